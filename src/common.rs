@@ -2,6 +2,25 @@
 use std::str::FromStr;
 
 
+/// A day's puzzle. Answers are returned as strings, since not every day's answer is a number. An
+/// empty string means the input has no answer.
+pub trait Puzzle {
+    /// The puzzle input bundled with the binary.
+    fn input(&self) -> &'static str;
+
+    fn part1(&self, input: &str) -> String;
+    fn part2(&self, input: &str) -> String;
+
+    /// Solves both parts for the bundled input and prints the answers. Days usually override this
+    /// to describe what the answers mean.
+    fn run(&self) {
+        let input = self.input();
+        println!("Part 1: {}", self.part1(input));
+        println!("Part 2: {}", self.part2(input));
+    }
+}
+
+
 /// Simple tokenizer and parser for space-separated data.
 ///
 /// My hate for regex burns with the passion of a thousand suns.
@@ -72,6 +91,25 @@ pub fn parse_separated_list<T: FromStr>(input: &str, separator: char) -> Result<
 }
 
 
+/// Calculates the manhattan (L1) distance between a and b.
+pub fn manhattan<const D: usize>(a: [i64; D], b: [i64; D]) -> u64 {
+    a.iter()
+        .zip(b.iter())
+        .map(|(a, b)| a.abs_diff(*b))
+        .sum()
+}
+
+/// Calculates the chebyshev (L∞) distance between a and b. That's the number of steps needed when
+/// diagonal moves are allowed.
+pub fn chebyshev<const D: usize>(a: [i64; D], b: [i64; D]) -> u64 {
+    a.iter()
+        .zip(b.iter())
+        .map(|(a, b)| a.abs_diff(*b))
+        .max()
+        .unwrap_or(0)
+}
+
+
 /// A blanket trait extending slices with the ability to acquire multiple mutable references to
 /// distinct indices within.
 pub trait GetMuts<'a> {
@@ -117,3 +155,20 @@ impl<'a, T> GetMuts<'a> for &'a mut [T] {
         })
     }
 }
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn metrics() {
+        assert_eq!(manhattan([0, 0], [0, 0]), 0);
+        assert_eq!(manhattan([1, 2], [4, -2]), 7);
+        assert_eq!(manhattan([-3, 5], [3, -5]), 16);
+
+        assert_eq!(chebyshev([0, 0], [0, 0]), 0);
+        assert_eq!(chebyshev([1, 2], [4, -2]), 4);
+        assert_eq!(chebyshev([-3, 5], [3, -5]), 10);
+    }
+}
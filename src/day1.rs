@@ -1,10 +1,12 @@
 
 //! Not really bothering with tests in this one.
 
-static INPUT: &str = include_str!("inputs/day1.txt");
+use crate::common::Puzzle;
+
 
-pub fn run() {
-    let elves_raw = INPUT.split("\n\n").filter(|s| !s.is_empty());
+/// Total calories carried by each elf, sorted ascending.
+fn elf_calories(input: &str) -> Vec<u32> {
+    let elves_raw = input.split("\n\n").filter(|s| !s.is_empty());
     let mut elves: Vec<u32> = elves_raw.map(|elf| {
         elf.split('\n')
             .filter(|s| !s.is_empty())
@@ -14,10 +16,35 @@ pub fn run() {
         .collect();
 
     elves.sort_unstable();
+    elves
+}
+
+
+static INPUT: &str = include_str!("inputs/day1.txt");
+
+
+pub struct Day1;
+
+impl Puzzle for Day1 {
+    fn input(&self) -> &'static str {
+        INPUT
+    }
+
+    fn part1(&self, input: &str) -> String {
+        elf_calories(input).last().unwrap().to_string()
+    }
+
+    fn part2(&self, input: &str) -> String {
+        elf_calories(input).iter().rev().take(3).sum::<u32>().to_string()
+    }
+
+    fn run(&self) {
+        let elves = elf_calories(INPUT);
 
-    let max_single_elf = elves.last().unwrap();
-    println!("Max calories carried by single elf: {max_single_elf}");
+        let max_single_elf = elves.last().unwrap();
+        println!("Max calories carried by single elf: {max_single_elf}");
 
-    let max_three_elves: u32 = elves.iter().rev().take(3).sum();
-    println!("Total calories carried by top three elves: {max_three_elves}");
+        let max_three_elves: u32 = elves.iter().rev().take(3).sum();
+        println!("Total calories carried by top three elves: {max_three_elves}");
+    }
 }
@@ -1,6 +1,9 @@
 
+use crate::common::Puzzle;
+
 use ndarray::Array2;
 
+use std::collections::HashMap;
 use std::str::FromStr;
 
 
@@ -10,6 +13,16 @@ enum Instruction {
     Addx(i32),
 }
 
+impl Instruction {
+    /// Number of cycles the instruction takes to complete.
+    fn cycles(&self) -> usize {
+        match self {
+            Self::Noop => 1,
+            Self::Addx(_) => 2,
+        }
+    }
+}
+
 #[derive(Debug)]
 struct InstructionParseError;
 
@@ -38,16 +51,27 @@ trait Screen {
     fn cycle(&mut self, cycle_number: usize, register: i32);
 }
 
-struct SignalAccumulator(i32);
+struct SignalAccumulator<'a> {
+    cycles: &'a [usize],
+    signal: i32,
+}
+
+impl<'a> SignalAccumulator<'a> {
+    const RELEVANT_CYCLES: &'static [usize] = &[20, 60, 100, 140, 180, 220];
 
-impl SignalAccumulator {
-    const RELEVANT_CYCLES: &[usize] = &[20, 60, 100, 140, 180, 220];
+    /// Creates an accumulator sampling the signal during the given cycles.
+    fn new(cycles: &'a [usize]) -> Self {
+        Self {
+            cycles,
+            signal: 0,
+        }
+    }
 }
 
-impl Screen for SignalAccumulator {
+impl Screen for SignalAccumulator<'_> {
     fn cycle(&mut self, cycle_number: usize, register: i32) {
-        if Self::RELEVANT_CYCLES.contains(&cycle_number) {
-            self.0 += cycle_number as i32 * register;
+        if self.cycles.contains(&cycle_number) {
+            self.signal += cycle_number as i32 * register;
         }
     }
 }
@@ -63,43 +87,130 @@ impl Screen for Array2<bool> {
     }
 }
 
+impl<A: Screen, B: Screen> Screen for (A, B) {
+    fn cycle(&mut self, cycle_number: usize, register: i32) {
+        self.0.cycle(cycle_number, register);
+        self.1.cycle(cycle_number, register);
+    }
+}
+
 
-fn run_program(program: &[Instruction], screen: &mut impl Screen) {
-    let mut cycle = 1;
-    let mut register = 1;
-    let mut pc = 0;
-    let mut step = 0;
-    while let Some(instruction) = program.get(pc) {
-        screen.cycle(cycle, register);
+/// Steps through a program one cycle at a time, yielding the cycle number and the register value
+/// during that cycle.
+struct Cycles<'a> {
+    program: &'a [Instruction],
+    cycle: usize,
+    register: i32,
+    pc: usize,
+    step: usize,
+}
+
+impl Iterator for Cycles<'_> {
+    type Item = (usize, i32);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let instruction = self.program.get(self.pc)?;
+        let during = (self.cycle, self.register);
 
         match instruction {
-            Instruction::Noop => pc += 1,
+            Instruction::Noop => self.pc += 1,
             Instruction::Addx(op) => {
-                if step == 0 {
-                    step = 1;
+                if self.step == 0 {
+                    self.step = 1;
                 } else {
-                    step = 0;
-                    register += op;
-                    pc += 1;
+                    self.step = 0;
+                    self.register += op;
+                    self.pc += 1;
                 }
             },
         }
 
-        cycle += 1;
+        self.cycle += 1;
+        Some(during)
     }
 }
 
-fn accumulate_signals(program: &[Instruction]) -> i32 {
-    let mut accum = SignalAccumulator(0);
+fn cycles(program: &[Instruction]) -> Cycles<'_> {
+    Cycles {
+        program,
+        cycle: 1,
+        register: 1,
+        pc: 0,
+        step: 0,
+    }
+}
+
+fn run_program(program: &[Instruction], screen: &mut impl Screen) {
+    for (cycle, register) in cycles(program) {
+        screen.cycle(cycle, register);
+    }
+}
+
+/// Number of cycles the program runs for.
+fn total_cycles(program: &[Instruction]) -> usize {
+    program.iter().map(Instruction::cycles).sum()
+}
+
+/// Sums up the signal strengths during the relevant cycles. Returns `None` if the program ends
+/// before the first of them, since the sum would be meaningless then.
+fn accumulate_signals(program: &[Instruction]) -> Option<i32> {
+    if total_cycles(program) < SignalAccumulator::RELEVANT_CYCLES[0] {
+        return None;
+    }
+
+    let mut accum = SignalAccumulator::new(SignalAccumulator::RELEVANT_CYCLES);
     run_program(program, &mut accum);
-    accum.0
+    Some(accum.signal)
 }
 
+/// Records one screen frame for every 240 cycles the program runs.
+struct FrameRecorder(Vec<Array2<bool>>);
+
+impl FrameRecorder {
+    const FRAME_DIMS: (usize, usize) = (6, 40);
+    const CYCLES_PER_FRAME: usize = Self::FRAME_DIMS.0 * Self::FRAME_DIMS.1;
+}
+
+impl Screen for FrameRecorder {
+    fn cycle(&mut self, cycle_number: usize, register: i32) {
+        let frame_index = (cycle_number - 1) / Self::CYCLES_PER_FRAME;
+        while self.0.len() <= frame_index {
+            self.0.push(Array2::from_elem(Self::FRAME_DIMS, false));
+        }
+        let frame_cycle = cycle_number - frame_index * Self::CYCLES_PER_FRAME;
+        self.0[frame_index].cycle(frame_cycle, register);
+    }
+}
+
+
+#[allow(dead_code)]
 fn render_screen(program: &[Instruction]) -> String {
     let mut screen = Array2::from_elem((6, 40), false);
     run_program(program, &mut screen);
+    screen_to_string(&screen)
+}
+
+/// Accumulates the signal and renders the screen in a single pass over the program. Like
+/// `accumulate_signals`, the signal is `None` if the program ends before the first relevant cycle.
+fn solve_both(program: &[Instruction]) -> (Option<i32>, String) {
+    let accum = SignalAccumulator::new(SignalAccumulator::RELEVANT_CYCLES);
+    let screen = Array2::from_elem((6, 40), false);
+    let mut both = (accum, screen);
+    run_program(program, &mut both);
+    let covered = total_cycles(program) >= SignalAccumulator::RELEVANT_CYCLES[0];
+    (covered.then_some(both.0.signal), screen_to_string(&both.1))
+}
 
-    // turn into string for printing
+/// Like `render_screen`, but instead of wrapping around, renders a new frame every 240 cycles.
+#[allow(dead_code)]
+fn render_frames(program: &[Instruction]) -> Vec<String> {
+    let mut recorder = FrameRecorder(Vec::new());
+    run_program(program, &mut recorder);
+    recorder.0.iter().map(screen_to_string).collect()
+}
+
+/// Turns the screen into a string for printing.
+fn screen_to_string(screen: &Array2<bool>) -> String {
     let mut string = String::new();
     for row in screen.outer_iter() {
         let row_chars = row.iter().map(|px| if *px {
@@ -114,15 +225,104 @@ fn render_screen(program: &[Instruction]) -> String {
 }
 
 
+/// Width of a glyph cell on the screen, including the blank column separating it from the next.
+const GLYPH_WIDTH: usize = 5;
+
+/// The letters commonly rendered by AoC puzzles, four pixels wide.
+const DEFAULT_FONT: &[(char, [&str; 6])] = &[
+    ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
+/// Encodes a glyph drawn with `#` for lit pixels. Each row becomes a bit mask with the leftmost
+/// pixel as the highest of `GLYPH_WIDTH` bits. Rows may be shorter than that.
+fn glyph_from_art(rows: [&str; 6]) -> [u8; 6] {
+    rows.map(|row| {
+        row.chars()
+            .take(GLYPH_WIDTH)
+            .enumerate()
+            .filter(|(_, c)| *c == '#')
+            .fold(0, |mask, (col, _)| mask | 1 << (GLYPH_WIDTH - 1 - col))
+    })
+}
+
+fn default_font() -> HashMap<[u8; 6], char> {
+    DEFAULT_FONT.iter()
+        .map(|(letter, rows)| (glyph_from_art(*rows), *letter))
+        .collect()
+}
+
+/// Encodes the glyph in the given glyph cell of the screen, like `glyph_from_art` does.
+fn glyph_at(screen: &Array2<bool>, index: usize) -> [u8; 6] {
+    let mut glyph = [0; 6];
+    for (mask, row) in glyph.iter_mut().zip(screen.outer_iter()) {
+        for col in 0..GLYPH_WIDTH {
+            if row.get(index * GLYPH_WIDTH + col).copied().unwrap_or(false) {
+                *mask |= 1 << (GLYPH_WIDTH - 1 - col);
+            }
+        }
+    }
+    glyph
+}
+
+/// Runs the program and reads the letters it renders using the given font. Glyphs not in the font
+/// are read as `?`.
+fn read_screen_with_font(program: &[Instruction], font: &HashMap<[u8; 6], char>) -> String {
+    let mut screen = Array2::from_elem((6, 40), false);
+    run_program(program, &mut screen);
+    (0..(screen.ncols() / GLYPH_WIDTH))
+        .map(|index| font.get(&glyph_at(&screen, index)).copied().unwrap_or('?'))
+        .collect()
+}
+
+/// Same as `read_screen_with_font`, using the default font.
+fn read_screen(program: &[Instruction]) -> String {
+    read_screen_with_font(program, &default_font())
+}
+
+
 static INPUT: &str = include_str!("inputs/day10.txt");
 
-pub fn run() {
-    let input = parse_input(INPUT);
-    let part1 = accumulate_signals(&input);
-    println!("Signal accumulated during the specified cycles: {part1}");
 
-    let part2 = render_screen(&input);
-    println!("Screen rendered:\n{part2}");
+pub struct Day10;
+
+impl Puzzle for Day10 {
+    fn input(&self) -> &'static str {
+        INPUT
+    }
+
+    fn part1(&self, input: &str) -> String {
+        accumulate_signals(&parse_input(input)).map_or_else(String::new, |s| s.to_string())
+    }
+
+    fn part2(&self, input: &str) -> String {
+        read_screen(&parse_input(input))
+    }
+
+    fn run(&self) {
+        let input = parse_input(INPUT);
+        let (part1, part2) = solve_both(&input);
+        match part1 {
+            Some(signal) => println!("Signal accumulated during the specified cycles: {signal}"),
+            None => eprintln!("Program ends before the first sampled cycle"),
+        }
+
+        println!("Screen rendered:\n{part2}");
+    }
 }
 
 
@@ -130,156 +330,230 @@ pub fn run() {
 mod test {
     use super::*;
 
+    const EXAMPLE: &str = "addx 15
+                           addx -11
+                           addx 6
+                           addx -3
+                           addx 5
+                           addx -1
+                           addx -8
+                           addx 13
+                           addx 4
+                           noop
+                           addx -1
+                           addx 5
+                           addx -1
+                           addx 5
+                           addx -1
+                           addx 5
+                           addx -1
+                           addx 5
+                           addx -1
+                           addx -35
+                           addx 1
+                           addx 24
+                           addx -19
+                           addx 1
+                           addx 16
+                           addx -11
+                           noop
+                           noop
+                           addx 21
+                           addx -15
+                           noop
+                           noop
+                           addx -3
+                           addx 9
+                           addx 1
+                           addx -3
+                           addx 8
+                           addx 1
+                           addx 5
+                           noop
+                           noop
+                           noop
+                           noop
+                           noop
+                           addx -36
+                           noop
+                           addx 1
+                           addx 7
+                           noop
+                           noop
+                           noop
+                           addx 2
+                           addx 6
+                           noop
+                           noop
+                           noop
+                           noop
+                           noop
+                           addx 1
+                           noop
+                           noop
+                           addx 7
+                           addx 1
+                           noop
+                           addx -13
+                           addx 13
+                           addx 7
+                           noop
+                           addx 1
+                           addx -33
+                           noop
+                           noop
+                           noop
+                           addx 2
+                           noop
+                           noop
+                           noop
+                           addx 8
+                           noop
+                           addx -1
+                           addx 2
+                           addx 1
+                           noop
+                           addx 17
+                           addx -9
+                           addx 1
+                           addx 1
+                           addx -3
+                           addx 11
+                           noop
+                           noop
+                           addx 1
+                           noop
+                           addx 1
+                           noop
+                           noop
+                           addx -13
+                           addx -19
+                           addx 1
+                           addx 3
+                           addx 26
+                           addx -30
+                           addx 12
+                           addx -1
+                           addx 3
+                           addx 1
+                           noop
+                           noop
+                           noop
+                           addx -9
+                           addx 18
+                           addx 1
+                           addx 2
+                           noop
+                           noop
+                           addx 9
+                           noop
+                           noop
+                           noop
+                           addx -1
+                           addx 2
+                           addx -37
+                           addx 1
+                           addx 3
+                           noop
+                           addx 15
+                           addx -21
+                           addx 22
+                           addx -6
+                           addx 1
+                           noop
+                           addx 2
+                           addx 1
+                           noop
+                           addx -10
+                           noop
+                           noop
+                           addx 20
+                           addx 1
+                           addx 2
+                           addx 2
+                           addx -6
+                           addx -11
+                           noop
+                           noop
+                           noop";
+
     #[test]
     fn example() {
-        let input = "addx 15
-                     addx -11
-                     addx 6
-                     addx -3
-                     addx 5
-                     addx -1
-                     addx -8
-                     addx 13
-                     addx 4
-                     noop
-                     addx -1
-                     addx 5
-                     addx -1
-                     addx 5
-                     addx -1
-                     addx 5
-                     addx -1
-                     addx 5
-                     addx -1
-                     addx -35
-                     addx 1
-                     addx 24
-                     addx -19
-                     addx 1
-                     addx 16
-                     addx -11
-                     noop
-                     noop
-                     addx 21
-                     addx -15
-                     noop
-                     noop
-                     addx -3
-                     addx 9
-                     addx 1
-                     addx -3
-                     addx 8
-                     addx 1
-                     addx 5
-                     noop
-                     noop
-                     noop
-                     noop
-                     noop
-                     addx -36
-                     noop
-                     addx 1
-                     addx 7
-                     noop
-                     noop
-                     noop
-                     addx 2
-                     addx 6
-                     noop
-                     noop
-                     noop
-                     noop
-                     noop
-                     addx 1
-                     noop
-                     noop
-                     addx 7
-                     addx 1
-                     noop
-                     addx -13
-                     addx 13
-                     addx 7
-                     noop
-                     addx 1
-                     addx -33
-                     noop
-                     noop
-                     noop
-                     addx 2
-                     noop
-                     noop
-                     noop
-                     addx 8
-                     noop
-                     addx -1
-                     addx 2
-                     addx 1
-                     noop
-                     addx 17
-                     addx -9
-                     addx 1
-                     addx 1
-                     addx -3
-                     addx 11
-                     noop
-                     noop
-                     addx 1
-                     noop
-                     addx 1
-                     noop
-                     noop
-                     addx -13
-                     addx -19
-                     addx 1
-                     addx 3
-                     addx 26
-                     addx -30
-                     addx 12
-                     addx -1
-                     addx 3
-                     addx 1
-                     noop
-                     noop
-                     noop
-                     addx -9
-                     addx 18
-                     addx 1
-                     addx 2
-                     noop
-                     noop
-                     addx 9
-                     noop
-                     noop
-                     noop
-                     addx -1
-                     addx 2
-                     addx -37
-                     addx 1
-                     addx 3
-                     noop
-                     addx 15
-                     addx -21
-                     addx 22
-                     addx -6
-                     addx 1
-                     noop
-                     addx 2
-                     addx 1
-                     noop
-                     addx -10
-                     noop
-                     noop
-                     addx 20
-                     addx 1
-                     addx 2
-                     addx 2
-                     addx -6
-                     addx -11
-                     noop
-                     noop
-                     noop";
+        let input = EXAMPLE;
         let prog = parse_input(input);
         let signal = accumulate_signals(&prog);
-        assert_eq!(signal, 13140);
+        assert_eq!(signal, Some(13140));
+    }
+
+    #[test]
+    fn stepper() {
+        let prog = parse_input(EXAMPLE);
+        let first = cycles(&prog).take(6).collect::<Vec<_>>();
+        assert_eq!(first, &[(1, 1), (2, 1), (3, 16), (4, 16), (5, 5), (6, 5)]);
+        assert_eq!(cycles(&prog).count(), 240);
+        assert_eq!(total_cycles(&prog), 240);
+        assert_eq!(cycles(&[]).next(), None);
+    }
+
+    #[test]
+    fn combined() {
+        let prog = parse_input(EXAMPLE);
+        let (signal, screen) = solve_both(&prog);
+        assert_eq!(signal, Some(13140));
+        assert_eq!(screen.lines().count(), 6);
+        assert_eq!(screen, render_screen(&prog));
+    }
+
+    #[test]
+    fn custom_cycles() {
+        let prog = parse_input("noop
+                                addx 3
+                                addx -5");
+        // x is 1 during the first three cycles, and 4 during the fourth and fifth
+        let mut accum = SignalAccumulator::new(&[1, 2, 3]);
+        run_program(&prog, &mut accum);
+        assert_eq!(accum.signal, 1 + 2 + 3);
+
+        let mut accum = SignalAccumulator::new(&[4, 5]);
+        run_program(&prog, &mut accum);
+        assert_eq!(accum.signal, 4 * 4 + 5 * 4);
+
+        assert_eq!(total_cycles(&prog), 5);
+        assert_eq!(total_cycles(&prog), cycles(&prog).count());
+        // too short to reach any of the sampled cycles
+        assert_eq!(accumulate_signals(&prog), None);
+        assert_eq!(solve_both(&prog).0, None);
+        assert_eq!(Day10.part1("noop\naddx 3\naddx -5"), "");
+    }
+
+    #[test]
+    fn ocr() {
+        let program = parse_input(INPUT);
+        assert_eq!(read_screen(&program), "EGJBGCFK");
+
+        let mut font = HashMap::new();
+        font.insert(glyph_from_art(["####", "#...", "###.", "#...", "#...", "####"]), 'e');
+        font.insert(glyph_from_art(["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]), 'k');
+        assert_eq!(read_screen_with_font(&program, &font), "e??????k");
+
+        // the example draws stripes, nothing readable
+        assert_eq!(read_screen(&parse_input(EXAMPLE)), "????????");
+        assert_eq!(glyph_from_art(["#..#", "", "", "", "", "#####"]),
+            [0b10010, 0, 0, 0, 0, 0b11111]);
+    }
+
+    #[test]
+    fn multiple_frames() {
+        let prog = parse_input(EXAMPLE);
+        let frames = render_frames(&prog);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0], render_screen(&prog));
+
+        // the example runs for exactly 240 cycles, so running it twice yields two full frames
+        let mut twice = prog.clone();
+        twice.extend_from_slice(&prog);
+        let frames = render_frames(&twice);
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0], render_screen(&prog));
     }
 }
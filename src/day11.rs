@@ -1,6 +1,7 @@
 
-use crate::common::{parse_separated_list, GetMuts};
+use crate::common::{parse_separated_list, GetMuts, Puzzle};
 
+use std::collections::HashMap;
 use std::str::FromStr;
 
 
@@ -12,7 +13,14 @@ enum MonkeyParseError {
     NumberParse,
     UnrecognizedLine,
     MissingPart,
+    /// A monkey definition lacks the line for the named field.
+    #[allow(dead_code)]
+    MissingField(&'static str),
     UnrecognizedOperator,
+    #[allow(dead_code)]
+    UnknownMonkey(usize),
+    #[allow(dead_code)]
+    DuplicateId(usize),
 }
 
 
@@ -56,6 +64,14 @@ impl OperationKind {
             Self::Multiply => lhs * rhs,
         }
     }
+
+    /// Like `evaluate`, but returns `None` on overflow.
+    fn try_evaluate(&self, lhs: WorryLevel, rhs: WorryLevel) -> Option<WorryLevel> {
+        match self {
+            Self::Add => lhs.checked_add(rhs),
+            Self::Multiply => lhs.checked_mul(rhs),
+        }
+    }
 }
 
 impl FromStr for OperationKind {
@@ -84,6 +100,13 @@ impl Operation {
         let rhs_value = self.rhs.evaluate(old);
         self.kind.evaluate(lhs_value, rhs_value)
     }
+
+    /// Like `evaluate`, but returns `None` if the new worry level doesn't fit.
+    fn try_evaluate(&self, old: WorryLevel) -> Option<WorryLevel> {
+        let lhs_value = self.lhs.evaluate(old);
+        let rhs_value = self.rhs.evaluate(old);
+        self.kind.try_evaluate(lhs_value, rhs_value)
+    }
 }
 
 impl FromStr for Operation {
@@ -151,12 +174,12 @@ impl FromStr for MonkeyDef {
         }
 
         Ok(Self {
-            id: id.ok_or(MonkeyParseError::MissingPart)?,
-            starting_items: items.ok_or(MonkeyParseError::MissingPart)?,
-            operation: operation.ok_or(MonkeyParseError::MissingPart)?,
-            divisible_test: test.ok_or(MonkeyParseError::MissingPart)?,
-            true_monkey: true_monkey.ok_or(MonkeyParseError::MissingPart)?,
-            false_monkey: false_monkey.ok_or(MonkeyParseError::MissingPart)?,
+            id: id.ok_or(MonkeyParseError::MissingField("id"))?,
+            starting_items: items.ok_or(MonkeyParseError::MissingField("starting items"))?,
+            operation: operation.ok_or(MonkeyParseError::MissingField("operation"))?,
+            divisible_test: test.ok_or(MonkeyParseError::MissingField("test"))?,
+            true_monkey: true_monkey.ok_or(MonkeyParseError::MissingField("if true"))?,
+            false_monkey: false_monkey.ok_or(MonkeyParseError::MissingField("if false"))?,
         })
     }
 }
@@ -167,37 +190,94 @@ fn parse_input(input: &str) -> Vec<MonkeyDef> {
         .map(|s| MonkeyDef::from_str(s).unwrap())
         .collect::<Vec<_>>();
     monkeys.sort_unstable_by_key(|m| m.id);
+    build_id_index(&monkeys).unwrap();
     monkeys
 }
 
+/// Maps monkey ids to their index in the given list of definitions. Also checks that no two monkeys
+/// share an id, and that every monkey that is being thrown to actually exists.
+fn build_id_index(monkeys: &[MonkeyDef]) -> Result<HashMap<usize, usize>, MonkeyParseError> {
+    let mut id_index = HashMap::with_capacity(monkeys.len());
+    for (index, monkey) in monkeys.iter().enumerate() {
+        if id_index.insert(monkey.id, index).is_some() {
+            return Err(MonkeyParseError::DuplicateId(monkey.id));
+        }
+    }
+    for monkey in monkeys {
+        for target in [monkey.true_monkey, monkey.false_monkey] {
+            if !id_index.contains_key(&target) {
+                return Err(MonkeyParseError::UnknownMonkey(target));
+            }
+        }
+    }
+    Ok(id_index)
+}
+
 
 #[derive(Debug, Clone)]
 struct Monkey<'a> {
     def: &'a MonkeyDef,
     items: Vec<WorryLevel>,
     inspected_item_count: usize,
+    /// Index of the monkey to throw to if the test passes. Resolved from the id in the definition.
+    true_idx: usize,
+    /// Index of the monkey to throw to if the test fails. Resolved from the id in the definition.
+    false_idx: usize,
 }
 
 impl<'a> Monkey<'a> {
-    fn new(def: &'a MonkeyDef) -> Self {
+    fn new(def: &'a MonkeyDef, id_index: &HashMap<usize, usize>) -> Self {
         Self {
             def,
             items: def.starting_items.clone(),
             inspected_item_count: 0,
+            true_idx: id_index[&def.true_monkey],
+            false_idx: id_index[&def.false_monkey],
         }
     }
 }
 
+/// Creates a monkey for each definition, resolving the throw targets by id.
+fn spawn_monkeys(input: &[MonkeyDef]) -> Vec<Monkey<'_>> {
+    let id_index = build_id_index(input).expect("Invalid monkey reference");
+    input.iter().map(|def| Monkey::new(def, &id_index)).collect()
+}
+
+
+/// Counts the items currently held by all monkeys.
+fn total_items(monkeys: &[Monkey<'_>]) -> usize {
+    monkeys.iter().map(|m| m.items.len()).sum()
+}
 
 /// Simulates a single round of monkey shenanigans.
 fn step_monkeys<F>(monkeys: &mut [Monkey<'_>], relief_function: &mut F)
+where
+    F: FnMut(WorryLevel) -> WorryLevel,
+{
+    #[cfg(debug_assertions)]
+    let total_before = total_items(monkeys);
+
+    // release builds would silently wrap around, so at least catch it in debug builds
+    if let Err(item) = try_step_monkeys(monkeys, relief_function, cfg!(debug_assertions)) {
+        panic!("Worry level overflowed while inspecting {item}");
+    }
+
+    #[cfg(debug_assertions)]
+    assert_eq!(total_before, total_items(monkeys), "Monkeys lost or created items");
+}
+
+/// Same as `step_monkeys`, but if `checked` is set, stops at the first item whose worry level
+/// would overflow during inspection and returns its worry level before the inspection. The round
+/// is left half done in that case.
+fn try_step_monkeys<F>(monkeys: &mut [Monkey<'_>], relief_function: &mut F, checked: bool)
+    -> Result<(), WorryLevel>
 where
     F: FnMut(WorryLevel) -> WorryLevel,
 {
     for current_idx in 0..monkeys.len() {
         // borrow all thre monkeys involved
-        let true_idx = monkeys[current_idx].def.true_monkey;
-        let false_idx = monkeys[current_idx].def.false_monkey;
+        let true_idx = monkeys[current_idx].true_idx;
+        let false_idx = monkeys[current_idx].false_idx;
         let [current_monkey, true_monkey, false_monkey] = monkeys
             .get_muts([current_idx, true_idx, false_idx]);
 
@@ -205,16 +285,76 @@ where
         current_monkey.inspected_item_count += current_monkey.items.len();
 
         for item in current_monkey.items.drain(..) {
-            let inspected_item = current_monkey.def.operation.evaluate(item);
+            let operation = &current_monkey.def.operation;
+            let inspected_item = if checked {
+                operation.try_evaluate(item).ok_or(item)?
+            } else {
+                operation.evaluate(item)
+            };
             let tested_item = relief_function(inspected_item);
 
-            if tested_item % current_monkey.def.divisible_test == 0 {
+            if tested_item.is_multiple_of(current_monkey.def.divisible_test) {
                 true_monkey.items.push(tested_item);
             } else {
                 false_monkey.items.push(tested_item);
             }
         }
     }
+    Ok(())
+}
+
+/// Checks that no items get lost or duplicated during the given number of rounds.
+#[allow(dead_code)]
+fn verify_conservation<F>(input: &[MonkeyDef], rounds: usize, mut relief_function: F) -> bool
+where
+    F: FnMut(WorryLevel) -> WorryLevel,
+{
+    let mut monkeys = spawn_monkeys(input);
+    let total = total_items(&monkeys);
+    for _ in 0..rounds {
+        // `step_monkeys` asserts the same in debug builds, which would panic before we could tell
+        try_step_monkeys(&mut monkeys, &mut relief_function, false).unwrap();
+        if total_items(&monkeys) != total {
+            return false;
+        }
+    }
+    true
+}
+
+
+/// A running simulation that can be advanced round by round.
+struct MonkeyGame<'a> {
+    monkeys: Vec<Monkey<'a>>,
+}
+
+impl<'a> MonkeyGame<'a> {
+    fn new(input: &'a [MonkeyDef]) -> Self {
+        Self {
+            monkeys: spawn_monkeys(input),
+        }
+    }
+
+    /// Simulates a single round.
+    fn round<F>(&mut self, relief_function: &mut F)
+    where
+        F: FnMut(WorryLevel) -> WorryLevel,
+    {
+        step_monkeys(&mut self.monkeys, relief_function);
+    }
+
+    #[allow(dead_code)]
+    fn state(&self) -> &[Monkey<'a>] {
+        &self.monkeys
+    }
+
+    /// Product of the inspection counts of the two most active monkeys.
+    fn monkey_business(&self) -> usize {
+        let mut counts = self.monkeys.iter()
+            .map(|m| m.inspected_item_count)
+            .collect::<Vec<_>>();
+        counts.sort_unstable();
+        counts.iter().rev().take(2).product()
+    }
 }
 
 
@@ -222,19 +362,137 @@ fn top_most_active_monkeys<F>(input: &[MonkeyDef], rounds: usize, mut relief_fun
 where
     F: FnMut(WorryLevel) -> WorryLevel,
 {
-    let mut monkeys = input.iter().map(Monkey::new).collect::<Vec<_>>();
+    let mut game = MonkeyGame::new(input);
 
     for _ in 0..rounds {
-        step_monkeys(&mut monkeys, &mut relief_function);
+        game.round(&mut relief_function);
     }
 
-    monkeys.sort_unstable_by_key(|m| m.inspected_item_count);
-    monkeys.iter().rev().take(2).map(|m| m.inspected_item_count).product()
+    game.monkey_business()
 }
 
+/// How worry levels are kept in check after each inspection.
+#[derive(Debug, Copy, Clone)]
+enum Relief {
+    /// Worry levels are divided by the given value, rounding down.
+    #[allow(dead_code)]
+    DivideBy(WorryLevel),
+    /// Same as `DivideBy`, but worry levels are first reduced modulo the divisor times the lcm of
+    /// all divisibility tests. Writing a = q·d·m + r, ⌊a/d⌋ = q·m + ⌊r/d⌋ ≡ ⌊r/d⌋ mod m, so items
+    /// are thrown to the same monkeys as with `DivideBy`.
+    DivideByModuloLcm(WorryLevel),
+    /// Worry levels are reduced modulo the lcm of all divisibility tests, which keeps them small
+    /// without changing where any item is thrown.
+    ModuloLcm,
+    /// Worry levels are left as they are. Overflows quickly.
+    #[allow(dead_code)]
+    Identity,
+}
+
+/// Least common multiple of all divisibility tests of the troop.
+fn divisible_test_lcm(input: &[MonkeyDef]) -> WorryLevel {
+    input.iter()
+        .map(|monkey| monkey.divisible_test)
+        .reduce(num::integer::lcm)
+        .unwrap()
+}
+
+impl Relief {
+    /// Turns this into a relief function for the given troop.
+    fn function(self, input: &[MonkeyDef]) -> impl Fn(WorryLevel) -> WorryLevel {
+        let lcm = divisible_test_lcm(input);
+        move |worry| match self {
+            Relief::DivideBy(d) => worry / d,
+            Relief::DivideByModuloLcm(d) => worry % (d * lcm) / d,
+            Relief::ModuloLcm => worry % lcm,
+            Relief::Identity => worry,
+        }
+    }
+}
+
+fn top_most_active(input: &[MonkeyDef], rounds: usize, relief: Relief) -> usize {
+    top_most_active_monkeys(input, rounds, relief.function(input))
+}
+
+/// Asserts that reducing worry levels modulo the lcm of the divisibility tests leads to the same
+/// inspection counts as leaving them alone, after every one of up to `rounds` rounds.
+///
+/// Without relief, worry levels grow extremely fast, so this stops early at the first round in
+/// which they would overflow. Returns the number of rounds that were actually compared.
+#[allow(dead_code)]
+fn assert_relief_equivalence(defs: &[MonkeyDef], rounds: usize) -> usize {
+    let mut identity = Relief::Identity.function(defs);
+    let mut modulo_lcm = Relief::ModuloLcm.function(defs);
+    let mut monkeys_identity = spawn_monkeys(defs);
+    let mut monkeys_modulo = monkeys_identity.clone();
+
+    for round in 0..rounds {
+        if try_step_monkeys(&mut monkeys_identity, &mut identity, true).is_err() {
+            return round;
+        }
+        step_monkeys(&mut monkeys_modulo, &mut modulo_lcm);
+
+        for (m1, m2) in monkeys_identity.iter().zip(monkeys_modulo.iter()) {
+            assert_eq!(m1.inspected_item_count, m2.inspected_item_count,
+                "Inspection counts of monkey {} differ after round {}", m1.def.id, round + 1);
+        }
+    }
+    rounds
+}
+
+
+/// Snapshot of a monkey's state after some rounds.
+#[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+struct MonkeyState {
+    id: usize,
+    items: Vec<WorryLevel>,
+    inspected: usize,
+}
+
+/// Runs the simulation for the given number of rounds and returns the final state of each monkey.
+#[allow(dead_code)]
+fn simulate_to_state<F>(input: &[MonkeyDef], rounds: usize, mut relief_function: F)
+    -> Vec<MonkeyState>
+where
+    F: FnMut(WorryLevel) -> WorryLevel,
+{
+    let mut game = MonkeyGame::new(input);
+
+    for _ in 0..rounds {
+        game.round(&mut relief_function);
+    }
+
+    game.monkeys.into_iter()
+        .map(|m| MonkeyState {
+            id: m.def.id,
+            items: m.items,
+            inspected: m.inspected_item_count,
+        })
+        .collect()
+}
+
+
+/// The items each monkey holds after the given number of rounds, in order of monkey ids.
+#[allow(dead_code)]
+fn worry_snapshot(input: &[MonkeyDef], rounds: usize, relief: Relief) -> Vec<Vec<WorryLevel>> {
+    simulate_to_state(input, rounds, relief.function(input)).into_iter()
+        .map(|state| state.items)
+        .collect()
+}
 
 fn calc_part_one(input: &[MonkeyDef]) -> usize {
-    top_most_active_monkeys(input, 20, |worry| worry / 3)
+    // reducing worry levels modulo the lcm alone guards against overflows, but does not work
+    // together with the division: ⌊a/3⌋ and ⌊(a mod m)/3⌋ are in general not congruent modulo m,
+    // so monkeys might start throwing items elsewhere. see the `division_breaks_lcm_reduction`
+    // test. reducing modulo 3·m instead does work.
+    top_most_active(input, 20, Relief::DivideByModuloLcm(3))
+}
+
+/// Monkey business after the given number of rounds without relief, keeping worry levels in check
+/// by reducing them modulo the lcm of all divisibility tests.
+fn monkey_business_no_relief(defs: &[MonkeyDef], rounds: usize) -> usize {
+    top_most_active(defs, rounds, Relief::ModuloLcm)
 }
 
 fn calc_part_two(input: &[MonkeyDef]) -> usize {
@@ -244,23 +502,36 @@ fn calc_part_two(input: &[MonkeyDef]) -> usize {
     // moduli with their least common multiple. that is probably a wobbly definition and less than
     // half of the proof needed here, but i've been stuck to long with this and i wanna continue
     // even though i don't fully understand what's going on (yet).
-    let div_test_lcm = input.iter()
-        .map(|monkey| monkey.divisible_test)
-        .reduce(num::integer::lcm)
-        .unwrap();
-    top_most_active_monkeys(input, 10000, |worry| worry % div_test_lcm)
+    monkey_business_no_relief(input, 10000)
 }
 
 
 static INPUT: &str = include_str!("inputs/day11.txt");
 
-pub fn run() {
-    let monkey_defs = parse_input(INPUT);
-    let part1 = calc_part_one(&monkey_defs);
-    println!("Items handled by top two active monkeys, multiplied together: {part1}");
 
-    let part2 = calc_part_two(&monkey_defs);
-    println!("Same, but without relief: {part2}");
+pub struct Day11;
+
+impl Puzzle for Day11 {
+    fn input(&self) -> &'static str {
+        INPUT
+    }
+
+    fn part1(&self, input: &str) -> String {
+        calc_part_one(&parse_input(input)).to_string()
+    }
+
+    fn part2(&self, input: &str) -> String {
+        calc_part_two(&parse_input(input)).to_string()
+    }
+
+    fn run(&self) {
+        let monkey_defs = parse_input(INPUT);
+        let part1 = calc_part_one(&monkey_defs);
+        println!("Items handled by top two active monkeys, multiplied together: {part1}");
+
+        let part2 = calc_part_two(&monkey_defs);
+        println!("Same, but without relief: {part2}");
+    }
 }
 
 
@@ -288,6 +559,68 @@ mod test {
         assert_eq!(monkey.divisible_test, 23);
         assert_eq!(monkey.true_monkey, 2);
         assert_eq!(monkey.false_monkey, 3);
+
+        // order of the lines does not matter
+        let input = "Monkey 0:
+                       If false: throw to monkey 3
+                       If true: throw to monkey 2
+                       Test: divisible by 23
+                       Operation: new = old * 19
+                       Starting items: 79, 98";
+        let monkey: MonkeyDef = input.parse().unwrap();
+        assert_eq!(monkey.divisible_test, 23);
+        assert_eq!(monkey.true_monkey, 2);
+
+        let input = "Monkey 0:
+                       Starting items: 79, 98
+                       Operation: new = old * 19
+                         If true: throw to monkey 2
+                         If false: throw to monkey 3";
+        let result = MonkeyDef::from_str(input);
+        assert!(matches!(result, Err(MonkeyParseError::MissingField("test"))));
+
+        let result = MonkeyDef::from_str("Monkey 0:");
+        assert!(matches!(result, Err(MonkeyParseError::MissingField("starting items"))));
+    }
+
+    #[test]
+    fn overflow() {
+        let square = Operation::from_str("new = old * old").unwrap();
+        assert_eq!(square.try_evaluate(3), Some(9));
+        assert_eq!(square.try_evaluate(u64::MAX - 5), None);
+        assert_eq!(square.try_evaluate(1 << 32), None);
+        assert_eq!(square.try_evaluate((1 << 32) - 1), Some(((1 << 32) - 1) * ((1 << 32) - 1)));
+
+        let add = Operation::from_str("new = old + 6").unwrap();
+        assert_eq!(add.try_evaluate(u64::MAX - 6), Some(u64::MAX));
+        assert_eq!(add.try_evaluate(u64::MAX - 5), None);
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "Worry level overflowed")]
+    fn overflow_without_relief() {
+        let monkeys = parse_input("Monkey 0:
+                                     Starting items: 79
+                                     Operation: new = old * old
+                                     Test: divisible by 2
+                                       If true: throw to monkey 1
+                                       If false: throw to monkey 2
+
+                                   Monkey 1:
+                                     Starting items: 79
+                                     Operation: new = old * old
+                                     Test: divisible by 2
+                                       If true: throw to monkey 2
+                                       If false: throw to monkey 0
+
+                                   Monkey 2:
+                                     Starting items: 79
+                                     Operation: new = old * old
+                                     Test: divisible by 2
+                                       If true: throw to monkey 0
+                                       If false: throw to monkey 1");
+        top_most_active(&monkeys, 10, Relief::Identity);
     }
 
     #[test]
@@ -325,6 +658,164 @@ mod test {
 
         let part2 = calc_part_two(&parsed);
         assert_eq!(part2, 2713310158);
+
+        // after round 1000, the two most active monkeys inspected 5204 and 5192 items
+        assert_eq!(monkey_business_no_relief(&parsed, 1000), 5204 * 5192);
+        assert_eq!(monkey_business_no_relief(&parsed, 10000), part2);
+
+        assert!(verify_conservation(&parsed, 20, |worry| worry / 3));
+
+        let mut game = MonkeyGame::new(&parsed);
+        game.round(&mut |worry| worry / 3);
+        let items = game.state().iter().map(|m| m.items.as_slice()).collect::<Vec<_>>();
+        assert_eq!(items, [&[20, 23, 27, 26][..], &[2080, 25, 167, 207, 401, 1046], &[], &[]]);
+
+        let snapshot = worry_snapshot(&parsed, 1, Relief::DivideBy(3));
+        assert_eq!(snapshot, [vec![20, 23, 27, 26], vec![2080, 25, 167, 207, 401, 1046], vec![],
+            vec![]]);
+        let snapshot = worry_snapshot(&parsed, 0, Relief::Identity);
+        assert_eq!(snapshot, [vec![79, 98], vec![54, 65, 75, 74], vec![79, 60, 97], vec![74]]);
+
+        let state = simulate_to_state(&parsed, 20, |worry| worry / 3);
+        let inspected = state.iter().map(|m| m.inspected).collect::<Vec<_>>();
+        assert_eq!(inspected, &[101, 95, 7, 105]);
+        assert_eq!(state[0].items, &[10, 12, 14, 26, 34]);
+        assert_eq!(state[1].items, &[245, 93, 53, 199, 115]);
+        assert!(state[2].items.is_empty());
+        assert!(state[3].items.is_empty());
+
+        // without relief, worry levels overflow during round 13
+        assert_eq!(assert_relief_equivalence(&parsed, 10), 10);
+        assert_eq!(assert_relief_equivalence(&parsed, 1000), 12);
+    }
+
+    #[test]
+    fn large_starting_items() {
+        let input = "Monkey 0:
+                       Starting items: 79, 98
+                       Operation: new = old * 19
+                       Test: divisible by 23
+                         If true: throw to monkey 2
+                         If false: throw to monkey 3
+
+                     Monkey 1:
+                       Starting items: 54, 65, 75, 74
+                       Operation: new = old + 6
+                       Test: divisible by 19
+                         If true: throw to monkey 2
+                         If false: throw to monkey 0
+
+                     Monkey 2:
+                       Starting items: 79, 60, 97
+                       Operation: new = old * old
+                       Test: divisible by 13
+                         If true: throw to monkey 1
+                         If false: throw to monkey 3
+
+                     Monkey 3:
+                       Starting items: 74
+                       Operation: new = old + 3
+                       Test: divisible by 17
+                         If true: throw to monkey 0
+                         If false: throw to monkey 1";
+        let mut parsed = parse_input(input);
+
+        // shifting every item by a multiple of 3·lcm does not change where it is thrown
+        let offset = 3 * divisible_test_lcm(&parsed) * 10000;
+        for def in &mut parsed {
+            for item in &mut def.starting_items {
+                *item += offset;
+            }
+        }
+
+        // dividing by three alone lets them overflow, while reducing them first keeps them small
+        let mut monkeys = spawn_monkeys(&parsed);
+        let mut relief = Relief::DivideBy(3).function(&parsed);
+        let overflowed = (0..20)
+            .any(|_| try_step_monkeys(&mut monkeys, &mut relief, true).is_err());
+        assert!(overflowed);
+        assert_eq!(calc_part_one(&parsed), 10605);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn state_json() {
+        let state = MonkeyState {
+            id: 3,
+            items: vec![1, 2],
+            inspected: 5,
+        };
+        let json = serde_json::to_string(&state).unwrap();
+        assert_eq!(json, r#"{"id":3,"items":[1,2],"inspected":5}"#);
+    }
+
+    #[test]
+    fn non_contiguous_ids() {
+        let input = "Monkey 5:
+                       Starting items: 74
+                       Operation: new = old + 3
+                       Test: divisible by 17
+                         If true: throw to monkey 0
+                         If false: throw to monkey 2
+
+                     Monkey 0:
+                       Starting items: 79, 98
+                       Operation: new = old * 19
+                       Test: divisible by 23
+                         If true: throw to monkey 2
+                         If false: throw to monkey 5
+
+                     Monkey 2:
+                       Starting items: 54, 65, 75, 74
+                       Operation: new = old + 6
+                       Test: divisible by 19
+                         If true: throw to monkey 5
+                         If false: throw to monkey 0";
+        let parsed = parse_input(input);
+        assert_eq!(parsed.iter().map(|m| m.id).collect::<Vec<_>>(), &[0, 2, 5]);
+
+        // same troop, with ids renumbered to be contiguous
+        let contiguous = input.replace("monkey 2", "monkey 1")
+            .replace("Monkey 2", "Monkey 1")
+            .replace("monkey 5", "monkey 2")
+            .replace("Monkey 5", "Monkey 2");
+        let contiguous = parse_input(&contiguous);
+        assert_eq!(calc_part_one(&parsed), calc_part_one(&contiguous));
+        assert_eq!(calc_part_two(&parsed), calc_part_two(&contiguous));
+
+        let broken = input.replace("throw to monkey 0", "throw to monkey 3");
+        let monkeys = broken.split("\n\n")
+            .map(|s| MonkeyDef::from_str(s).unwrap())
+            .collect::<Vec<_>>();
+        assert!(matches!(build_id_index(&monkeys), Err(MonkeyParseError::UnknownMonkey(3))));
+    }
+
+    #[test]
+    fn duplicate_ids() {
+        let input = "Monkey 1:
+                       Starting items: 79, 98
+                       Operation: new = old * 19
+                       Test: divisible by 23
+                         If true: throw to monkey 0
+                         If false: throw to monkey 1
+
+                     Monkey 0:
+                       Starting items: 74
+                       Operation: new = old + 3
+                       Test: divisible by 17
+                         If true: throw to monkey 1
+                         If false: throw to monkey 1
+
+                     Monkey 1:
+                       Starting items: 54, 65
+                       Operation: new = old + 6
+                       Test: divisible by 19
+                         If true: throw to monkey 0
+                         If false: throw to monkey 0";
+        let monkeys = input.split("\n\n")
+            .map(|s| MonkeyDef::from_str(s).unwrap())
+            .collect::<Vec<_>>();
+        assert!(matches!(build_id_index(&monkeys), Err(MonkeyParseError::DuplicateId(1))));
     }
 
 
@@ -336,7 +827,7 @@ mod test {
         F1: FnMut(WorryLevel) -> WorryLevel,
         F2: FnMut(WorryLevel) -> WorryLevel,
     {
-        let mut monkeys_1 = input.iter().map(Monkey::new).collect::<Vec<_>>();
+        let mut monkeys_1 = spawn_monkeys(input);
         let mut monkeys_2 = monkeys_1.clone();
 
         for _ in 0..max_rounds {
@@ -387,13 +878,49 @@ mod test {
 
         // this check is very sensitive to larger round counts. it can overflow even with moderate
         // counts.
-        let lcm = parsed.iter()
-            .map(|monkey| monkey.divisible_test)
-            .reduce(num::integer::lcm)
-            .unwrap();
+        let lcm = divisible_test_lcm(&parsed);
         let f1 = |w| w;
         let f2 = |w| w % lcm;
         let equiv = check_relief_equivalence(&parsed, 6, f1, f2);
         assert!(equiv);
+
+        for rounds in 0..=6 {
+            assert_eq!(top_most_active(&parsed, rounds, Relief::Identity),
+                top_most_active(&parsed, rounds, Relief::ModuloLcm));
+        }
+        assert_eq!(assert_relief_equivalence(&parsed, 6), 6);
+    }
+
+    #[test]
+    fn division_breaks_lcm_reduction() {
+        let input = "Monkey 0:
+                       Starting items: 117
+                       Operation: new = old + 0
+                       Test: divisible by 3
+                         If true: throw to monkey 1
+                         If false: throw to monkey 2
+
+                     Monkey 1:
+                       Starting items: 1
+                       Operation: new = old + 0
+                       Test: divisible by 5
+                         If true: throw to monkey 0
+                         If false: throw to monkey 2
+
+                     Monkey 2:
+                       Starting items: 1
+                       Operation: new = old + 0
+                       Test: divisible by 7
+                         If true: throw to monkey 0
+                         If false: throw to monkey 1";
+        let parsed = parse_input(input);
+        let lcm = divisible_test_lcm(&parsed);
+        assert_eq!(lcm, 105);
+
+        // 117 / 3 = 39 goes to monkey 1, but (117 mod 105) / 3 = 4 goes to monkey 2
+        let f1 = |w| w / 3;
+        let f2 = |w| (w % lcm) / 3;
+        let equiv = check_relief_equivalence(&parsed, 1, f1, f2);
+        assert!(!equiv);
     }
 }
@@ -1,8 +1,10 @@
 
+use crate::common::{manhattan, Puzzle};
+
 use ndarray::Array2;
 
 use std::cmp::Ordering;
-use std::collections::{BinaryHeap, HashMap};
+use std::collections::{BinaryHeap, HashMap, VecDeque};
 
 
 type Point = [usize; 2];
@@ -59,12 +61,49 @@ impl Iterator for FourNeighborhood {
 
 
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct Map {
     height_map: Array2<u8>,
     start: Point,
     end: Point,
 }
 
+impl Map {
+    /// Builds a map from numeric heights directly, with 0 being the lowest point. Panics if start
+    /// or end lie outside the map.
+    #[allow(dead_code)]
+    fn from_heights(heights: Array2<u8>, start: Point, end: Point) -> Map {
+        let map = Map {
+            height_map: heights,
+            start,
+            end,
+        };
+        assert!(map.in_bounds(start), "Start point {start:?} out of bounds");
+        assert!(map.in_bounds(end), "End point {end:?} out of bounds");
+        map
+    }
+
+    #[allow(dead_code)]
+    fn start(&self) -> Point {
+        self.start
+    }
+
+    #[allow(dead_code)]
+    fn end(&self) -> Point {
+        self.end
+    }
+
+    /// Number of rows and columns of the map.
+    fn dims(&self) -> (usize, usize) {
+        self.height_map.dim()
+    }
+
+    fn in_bounds(&self, point: Point) -> bool {
+        let (rows, cols) = self.dims();
+        point[0] < rows && point[1] < cols
+    }
+}
+
 fn parse_input(input: &str) -> Map {
     let lines = input.lines().map(str::trim);
     let rows = lines.clone().count();
@@ -99,6 +138,27 @@ fn parse_input(input: &str) -> Map {
 }
 
 
+/// Rule deciding between which heights one may step.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+enum ClimbRule {
+    /// Climb up at most one unit, but descend any number of units. That's the puzzle's rule.
+    #[default]
+    UnlimitedDescent,
+    /// Climb up or descend at most one unit.
+    #[allow(dead_code)]
+    AtMostOne,
+}
+
+impl ClimbRule {
+    fn allows(&self, from_height: u8, to_height: u8) -> bool {
+        match self {
+            Self::UnlimitedDescent => to_height <= from_height.saturating_add(1),
+            Self::AtMostOne => to_height.abs_diff(from_height) <= 1,
+        }
+    }
+}
+
+
 struct NodeMeta {
     /// The point by which we reached this node. Used for backtracking.
     predecessor: Option<Point>,
@@ -161,6 +221,7 @@ struct AStar {
     node_meta: HashMap<Point, NodeMeta>,
     queue: BinaryHeap<QueueNode>,
     path_out: Vec<Point>,
+    climb_rule: ClimbRule,
 }
 
 impl AStar {
@@ -169,9 +230,39 @@ impl AStar {
             node_meta: HashMap::new(),
             queue: BinaryHeap::new(),
             path_out: Vec::new(),
+            climb_rule: ClimbRule::default(),
+        }
+    }
+
+    /// Pre-allocates the buffers for a search over the given number of nodes, so the first run
+    /// doesn't need to grow them.
+    fn with_capacity(nodes: usize) -> Self {
+        Self {
+            node_meta: HashMap::with_capacity(nodes),
+            queue: BinaryHeap::with_capacity(nodes),
+            path_out: Vec::with_capacity(nodes),
+            climb_rule: ClimbRule::default(),
         }
     }
 
+    /// Empties all buffers, but keeps their allocated memory around.
+    fn clear(&mut self) {
+        self.node_meta.clear();
+        self.queue.clear();
+        self.path_out.clear();
+    }
+
+    /// Number of nodes the buffers can hold without reallocating.
+    #[allow(dead_code)]
+    fn capacity(&self) -> usize {
+        self.node_meta.capacity().min(self.queue.capacity())
+    }
+
+    #[allow(dead_code)]
+    fn set_climb_rule(&mut self, rule: ClimbRule) {
+        self.climb_rule = rule;
+    }
+
     /// Backtrack node meta and update path output buffer.
     fn backtrack(&mut self, end: Point) {
         self.path_out.clear();
@@ -185,17 +276,10 @@ impl AStar {
         }
     }
 
-    /// Calculates the manhattan distance between a and be. The classic A* heuristic on a
-    /// 4-connected grid, apparently.
-    fn manhattan_distance(a: Point, b: Point) -> usize {
-        a.iter()
-            .zip(b.iter())
-            .map(|(a, b)| if a > b {
-                a - b
-            } else {
-                b - a
-            })
-            .sum()
+    /// The classic A* heuristic on a 4-connected grid, apparently.
+    fn heuristic(a: Point, b: Point) -> usize {
+        let to_signed = |p: Point| p.map(|c| c as i64);
+        manhattan(to_signed(a), to_signed(b)) as usize
     }
 
     /// Runs the A* algorithm on the map.
@@ -203,9 +287,35 @@ impl AStar {
     /// Finds a path from start to end, including both start and end. The returned path is reversed
     /// because of algorithms.
     fn run(&mut self, map: &Array2<u8>, start: Point, end: Point) -> Option<&[Point]> {
-        self.queue.clear();
+        self.search(map, start, end, None)?;
+        Some(&self.path_out)
+    }
+
+    /// Like `run`, but entering a cell costs whatever the cost grid says instead of 1. Returns the
+    /// total cost along with the path.
+    #[allow(dead_code)]
+    fn run_weighted(&mut self, heights: &Array2<u8>, costs: &Array2<usize>, start: Point,
+        end: Point) -> Option<(usize, &[Point])>
+    {
+        assert_eq!(heights.raw_dim(), costs.raw_dim(), "Cost grid does not match the map");
+
+        // the heuristic must never overestimate, so scale it by the cheapest possible step
+        let min_cost = costs.iter().copied().min().unwrap_or(0);
+        let cost = self.search(heights, start, end, Some((costs, min_cost)))?;
+        Some((cost, &self.path_out))
+    }
+
+    /// The actual A* search. Returns the cost of the found path, and leaves the path itself in the
+    /// output buffer. Without a cost grid, every step costs 1. The cost grid is accompanied by the
+    /// cheapest step cost, used to scale the heuristic.
+    fn search(&mut self, map: &Array2<u8>, start: Point, end: Point,
+        costs: Option<(&Array2<usize>, usize)>) -> Option<usize>
+    {
+        let weighted = costs.is_some();
+        let edge_cost = |point: Point| costs.map_or(1, |(grid, _)| grid[point]);
+        let heuristic_scale = costs.map_or(1, |(_, min_cost)| min_cost);
+        self.clear();
         self.queue.push(QueueNode::new(start, 0));
-        self.node_meta.clear();
         self.node_meta.insert(start, NodeMeta {
             predecessor: None,
             cost: 0,
@@ -216,7 +326,7 @@ impl AStar {
             if current.point == end {
                 // found path. backtrack
                 self.backtrack(end);
-                return Some(&self.path_out);
+                return Some(self.node_meta[&end].cost);
             }
 
             let current_height = map[current.point];
@@ -228,14 +338,13 @@ impl AStar {
 
             for neighbor in FourNeighborhood::new(current.point, map.nrows(), map.ncols()) {
                 let neighbor_height = map[neighbor];
-                if neighbor_height > current_height + 1 {
-                    // may only climb up 1 unit. ignore this neighbor
+                if !self.climb_rule.allows(current_height, neighbor_height) {
+                    // can't step there. ignore this neighbor
                     continue;
                 }
 
                 // calculate part cost for this neighbor via current node
-                let edge_cost = 1;
-                let neighbor_cost = current_cost.saturating_add(edge_cost);
+                let neighbor_cost = current_cost.saturating_add(edge_cost(neighbor));
 
                 let neighbor_meta = self.node_meta.entry(neighbor).or_default();
                 if neighbor_cost < neighbor_meta.cost {
@@ -244,10 +353,12 @@ impl AStar {
                     neighbor_meta.cost = neighbor_cost;
 
                     // estimate total cost for queue priority
-                    let heuristic = Self::manhattan_distance(neighbor, end);
+                    let heuristic = Self::heuristic(neighbor, end) * heuristic_scale;
                     let neighbor_total_cost = neighbor_cost + heuristic;
 
-                    if !neighbor_meta.in_queue {
+                    // with uneven costs, a queued neighbor may just have gotten a better priority.
+                    // queue it again instead of searching the queue. the stale entry is harmless.
+                    if !neighbor_meta.in_queue || weighted {
                         neighbor_meta.in_queue = true;
                         self.queue.push(QueueNode::new(neighbor, neighbor_total_cost));
                     }
@@ -260,12 +371,19 @@ impl AStar {
     }
 }
 
+/// Number of steps on the shortest path from the map's start to its end.
+fn shortest_path_len(map: &Map) -> usize {
+    let mut a_star = AStar::with_capacity(map.height_map.len());
+    let path = a_star.run(&map.height_map, map.start, map.end).unwrap();
+    path.len() - 1
+}
+
 /// Ignores the map-defined start point and instead checks all points with height 'a'.
 ///
 /// Yeah, yeah, should've went with Dijkstra. But let's roll with A* for the lols. Oh god it's so
 /// slow.
 fn find_min_path_len(map: &Map) -> usize {
-    let mut a_star = AStar::new();
+    let mut a_star = AStar::with_capacity(map.height_map.len());
     map.height_map.indexed_iter()
         .filter_map(|(index, height)| (*height == 0).then_some(index))
         .filter_map(|start| {
@@ -276,16 +394,115 @@ fn find_min_path_len(map: &Map) -> usize {
         .unwrap()
 }
 
+/// Number of steps needed to get from one point to another. None if there is no path or either
+/// point is outside the map.
+#[allow(dead_code)]
+fn path_length(map: &Map, from: Point, to: Point) -> Option<usize> {
+    if !map.in_bounds(from) || !map.in_bounds(to) {
+        return None;
+    }
+    let mut a_star = AStar::new();
+    a_star.run(&map.height_map, from, to).map(|path| path.len() - 1)
+}
+
+/// Finds every shortest path from start to end. Paths include both start and end, in that order.
+///
+/// Uses a breadth first search that remembers all predecessors at optimal distance, then walks the
+/// resulting DAG back from the end. The number of paths can grow exponentially with the map size,
+/// so this is only fit for small maps.
+#[allow(dead_code)]
+fn all_shortest_paths(map: &Map, start: Point, end: Point) -> Vec<Vec<Point>> {
+    let height_map = &map.height_map;
+    let (rows, cols) = map.dims();
+    let rule = ClimbRule::default();
+    let mut distance = HashMap::from([(start, 0)]);
+    let mut predecessors: HashMap<Point, Vec<Point>> = HashMap::new();
+    let mut queue = VecDeque::from([start]);
+    while let Some(current) = queue.pop_front() {
+        if current == end {
+            // everything still queued is at least as far away as the end, so can't be on a path
+            break;
+        }
+        let current_distance = distance[&current];
+        for neighbor in FourNeighborhood::new(current, rows, cols) {
+            if !rule.allows(height_map[current], height_map[neighbor]) {
+                continue;
+            }
+            let neighbor_distance = *distance.entry(neighbor).or_insert_with(|| {
+                queue.push_back(neighbor);
+                current_distance + 1
+            });
+            if neighbor_distance == current_distance + 1 {
+                predecessors.entry(neighbor).or_default().push(current);
+            }
+        }
+    }
+
+    if !distance.contains_key(&end) {
+        return Vec::new();
+    }
+
+    // enumerate paths depth first, building them back to front
+    let mut paths = Vec::new();
+    let mut partial = vec![end];
+    let mut stack = vec![(end, 0)];
+    while let Some((point, pred_index)) = stack.pop() {
+        if point == start {
+            paths.push(partial.iter().rev().copied().collect());
+            partial.pop();
+            continue;
+        }
+        match predecessors.get(&point).and_then(|preds| preds.get(pred_index)) {
+            Some(&pred) => {
+                stack.push((point, pred_index + 1));
+                stack.push((pred, 0));
+                partial.push(pred);
+            },
+            None => {
+                partial.pop();
+            },
+        }
+    }
+    paths
+}
+
+/// Counts how often the direction of movement changes along the path.
+#[allow(dead_code)]
+fn count_turns(path: &[Point]) -> usize {
+    let direction = |from: Point, to: Point| {
+        [to[0] as isize - from[0] as isize, to[1] as isize - from[1] as isize]
+    };
+    path.windows(3)
+        .filter(|w| direction(w[0], w[1]) != direction(w[1], w[2]))
+        .count()
+}
+
 static INPUT: &str = include_str!("inputs/day12.txt");
 
-pub fn run() {
-    let map = parse_input(INPUT);
-    let mut a_star = AStar::new();
-    let path = a_star.run(&map.height_map, map.start, map.end).unwrap();
-    println!("The shortest path from start to end is {} steps long", path.len() - 1);
 
-    let min_path = find_min_path_len(&map);
-    println!("Minimum path starting from an 'a' node: {min_path}");
+pub struct Day12;
+
+impl Puzzle for Day12 {
+    fn input(&self) -> &'static str {
+        INPUT
+    }
+
+    fn part1(&self, input: &str) -> String {
+        shortest_path_len(&parse_input(input)).to_string()
+    }
+
+    fn part2(&self, input: &str) -> String {
+        find_min_path_len(&parse_input(input)).to_string()
+    }
+
+    fn run(&self) {
+        let map = parse_input(INPUT);
+        let part1 = shortest_path_len(&map);
+        println!("The shortest path from start to end is {part1} steps long");
+
+        let min_path = find_min_path_len(&map);
+        println!("Minimum path starting from an 'a' node: {min_path}");
+    }
 }
 
 
@@ -303,6 +520,10 @@ mod test {
         let parsed = parse_input(input);
         assert_eq!(parsed.start, [0, 0]);
         assert_eq!(parsed.end, [2, 5]);
+        assert_eq!(parsed.dims(), (5, 8));
+        assert!(parsed.in_bounds([4, 7]));
+        assert!(!parsed.in_bounds([5, 0]));
+        assert!(!parsed.in_bounds([0, 8]));
 
         let mut a_star = AStar::new();
         let path = a_star.run(&parsed.height_map, parsed.start, parsed.end).unwrap();
@@ -310,5 +531,189 @@ mod test {
 
         let min_path = find_min_path_len(&parsed);
         assert_eq!(min_path, 29);
+
+        assert_eq!(path_length(&parsed, parsed.start(), parsed.end()), Some(31));
+        assert_eq!(path_length(&parsed, [1, 1], [2, 2]), Some(2));
+        assert_eq!(path_length(&parsed, [3, 3], [3, 3]), Some(0));
+        assert_eq!(path_length(&parsed, [0, 0], [5, 0]), None);
+        assert_eq!(path_length(&parsed, [0, 8], [0, 0]), None);
+    }
+
+    #[test]
+    fn all_paths() {
+        let parsed = parse_input("Sabqponm
+                                  abcryxxl
+                                  accszExk
+                                  acctuvwj
+                                  abdefghi");
+        let paths = all_shortest_paths(&parsed, parsed.start, parsed.end);
+        assert!(paths.len() > 1);
+        for path in &paths {
+            assert_eq!(path.len() - 1, 31);
+            assert_eq!(path.first(), Some(&parsed.start));
+            assert_eq!(path.last(), Some(&parsed.end));
+            for step in path.windows(2) {
+                let distance = step[0][0].abs_diff(step[1][0]) + step[0][1].abs_diff(step[1][1]);
+                assert_eq!(distance, 1);
+                assert!(ClimbRule::default()
+                    .allows(parsed.height_map[step[0]], parsed.height_map[step[1]]));
+            }
+        }
+        let mut deduped = paths.clone();
+        deduped.sort();
+        deduped.dedup();
+        assert_eq!(deduped.len(), paths.len());
+
+        assert_eq!(all_shortest_paths(&parsed, [3, 3], [3, 3]), vec![vec![[3, 3]]]);
+        assert!(all_shortest_paths(&parsed, [0, 0], [1, 3]).len() > 1);
+
+        let walled_in = parse_input("Sz
+                                     zE");
+        assert!(all_shortest_paths(&walled_in, walled_in.start, walled_in.end).is_empty());
+    }
+
+    #[test]
+    fn turns() {
+        let path = [[0, 0], [0, 1], [0, 2], [1, 2], [2, 2]];
+        assert_eq!(count_turns(&path), 1);
+        let zigzag = [[0, 0], [0, 1], [1, 1], [1, 2], [2, 2]];
+        assert_eq!(count_turns(&zigzag), 3);
+        assert_eq!(count_turns(&path[..3]), 0);
+        assert_eq!(count_turns(&path[..1]), 0);
+
+        // the example's shortest path spirals inwards
+        let parsed = parse_input("Sabqponm
+                                  abcryxxl
+                                  accszExk
+                                  acctuvwj
+                                  abdefghi");
+        let mut a_star = AStar::new();
+        let path = a_star.run(&parsed.height_map, parsed.start, parsed.end).unwrap();
+        assert!(count_turns(path) >= 8);
+    }
+
+    #[test]
+    fn numeric_heights() {
+        let heights = ndarray::array![
+            [0, 1, 2],
+            [5, 4, 3],
+            [6, 9, 9],
+        ];
+        let map = Map::from_heights(heights, [0, 0], [2, 0]);
+        let mut a_star = AStar::new();
+        let path = a_star.run(&map.height_map, map.start(), map.end()).unwrap();
+        assert_eq!(path.len() - 1, 6);
+        assert_eq!(path.first(), Some(&[2, 0]));
+
+        // the full range of heights is allowed
+        let heights = ndarray::array![[254, 255, 255, 0]];
+        let map = Map::from_heights(heights, [0, 0], [0, 3]);
+        let path = a_star.run(&map.height_map, map.start(), map.end()).unwrap();
+        assert_eq!(path.len() - 1, 3);
+    }
+
+    #[test]
+    #[should_panic]
+    fn numeric_heights_out_of_bounds() {
+        Map::from_heights(Array2::from_elem((2, 2), 0), [0, 0], [2, 0]);
+    }
+
+    #[test]
+    fn weighted() {
+        let heights = Array2::from_elem((3, 5), 0);
+        let mut costs = Array2::from_elem((3, 5), 1);
+        let (start, end) = ([1, 0], [1, 4]);
+
+        let mut a_star = AStar::new();
+        let path = a_star.run(&heights, start, end).unwrap();
+        assert_eq!(path.len() - 1, 4);
+
+        // with uniform costs, the weighted search agrees
+        let (cost, path) = a_star.run_weighted(&heights, &costs, start, end).unwrap();
+        assert_eq!(cost, 4);
+        assert_eq!(path.len() - 1, 4);
+
+        // a swamp in the middle row makes the detour through the top or bottom row cheaper
+        costs.slice_mut(ndarray::s![1, 1..4]).fill(10);
+        let (cost, path) = a_star.run_weighted(&heights, &costs, start, end).unwrap();
+        assert_eq!(cost, 6);
+        assert_eq!(path.len() - 1, 6);
+        assert_eq!(path.first(), Some(&end));
+        assert_eq!(path.last(), Some(&start));
+        assert!(path.iter().all(|p| costs[*p] == 1));
+
+        // a cheap road pulls the path towards it, even if it's longer
+        let mut costs = Array2::from_elem((3, 5), 5);
+        costs.row_mut(2).fill(1);
+        costs[[1, 4]] = 1;
+        let (cost, path) = a_star.run_weighted(&heights, &costs, start, end).unwrap();
+        assert_eq!(cost, 1 + 4 + 1);
+        assert_eq!(path.len() - 1, 6);
+    }
+
+    #[test]
+    fn buffer_reuse() {
+        let parsed = parse_input("Sabqponm
+                                  abcryxxl
+                                  accszExk
+                                  acctuvwj
+                                  abdefghi");
+        let nodes = parsed.height_map.len();
+        let mut a_star = AStar::with_capacity(nodes);
+        let initial_capacity = a_star.capacity();
+        assert!(initial_capacity >= nodes);
+
+        assert!(a_star.run(&parsed.height_map, parsed.start, parsed.end).is_some());
+        let capacity = a_star.capacity();
+        assert_eq!(capacity, initial_capacity);
+
+        let path = a_star.run(&parsed.height_map, parsed.start, parsed.end).unwrap();
+        assert_eq!(path.len() - 1, 31);
+        assert_eq!(a_star.capacity(), capacity);
+
+        a_star.clear();
+        assert!(a_star.node_meta.is_empty());
+        assert_eq!(a_star.capacity(), capacity);
+    }
+
+    #[test]
+    fn climb_rules() {
+        assert!(ClimbRule::default() == ClimbRule::UnlimitedDescent);
+        assert!(ClimbRule::UnlimitedDescent.allows(25, 0));
+        assert!(!ClimbRule::UnlimitedDescent.allows(0, 2));
+        assert!(!ClimbRule::AtMostOne.allows(25, 0));
+        assert!(ClimbRule::AtMostOne.allows(3, 2));
+
+        // jumping down the cliff is a shortcut, but only with unlimited descent
+        let height_map = ndarray::arr2(&[
+            [3, 0],
+            [2, 1]]);
+        let mut a_star = AStar::new();
+        let path = a_star.run(&height_map, [0, 0], [0, 1]).unwrap();
+        assert_eq!(path.len() - 1, 1);
+
+        a_star.set_climb_rule(ClimbRule::AtMostOne);
+        let path = a_star.run(&height_map, [0, 0], [0, 1]).unwrap();
+        assert_eq!(path.len() - 1, 3);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let input = "Sabqponm
+                     abcryxxl
+                     accszExk
+                     acctuvwj
+                     abdefghi";
+        let parsed = parse_input(input);
+        let json = serde_json::to_string(&parsed).unwrap();
+        let loaded: Map = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.height_map, parsed.height_map);
+        assert_eq!(loaded.start, parsed.start);
+        assert_eq!(loaded.end, parsed.end);
+
+        let mut a_star = AStar::new();
+        let path = a_star.run(&loaded.height_map, loaded.start, loaded.end).unwrap();
+        assert_eq!(path.len() - 1, 31);
     }
 }
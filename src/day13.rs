@@ -1,4 +1,7 @@
 
+use crate::common::Puzzle;
+
+use std::cell::Cell;
 use std::cmp::Ordering;
 
 
@@ -104,6 +107,24 @@ impl Packet {
     fn iter(&self) -> PacketIter<'_> {
         self.slice().iter()
     }
+
+    /// Maximum nesting depth of lists in this packet. The outer list counts, so `[]` has depth 1.
+    #[allow(dead_code)]
+    fn depth(&self) -> usize {
+        // stack of flat indices at which the currently entered lists end
+        let mut list_ends: Vec<usize> = Vec::new();
+        let mut max_depth = 0;
+        for (index, item) in self.0.iter().enumerate() {
+            while list_ends.last().is_some_and(|end| *end < index) {
+                list_ends.pop();
+            }
+            if let FlatPacketItem::List{ flat_size, .. } = item {
+                list_ends.push(index + flat_size);
+                max_depth = max_depth.max(list_ends.len());
+            }
+        }
+        max_depth
+    }
 }
 
 impl PartialOrd for Packet {
@@ -127,6 +148,18 @@ enum PacketItem<'a> {
 }
 
 
+/// How to compare a number against a list.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+enum CompareMode {
+    /// Wrap the number in a single-element list, as the puzzle describes.
+    #[default]
+    Coerce,
+    /// Consider mismatched items equal and move on to the next pair.
+    #[allow(dead_code)]
+    MismatchEqual,
+}
+
+
 /// A slice of a packet.
 #[derive(Debug, Copy, Clone)]
 struct PacketSlice<'a>(&'a [FlatPacketItem]);
@@ -142,21 +175,45 @@ impl<'a> PacketSlice<'a> {
     /// rhs. The equal case has never been described, but this routine should coincide with the
     /// simpler equality case.
     fn packet_cmp(&self, rhs: Self) -> Ordering {
+        self.packet_cmp_counted(rhs, None)
+    }
+
+    /// Same as `packet_cmp`, but increments the counter (if given) once for every pair of items
+    /// that gets compared, including those in nested lists.
+    fn packet_cmp_counted(&self, rhs: Self, counter: Option<&Cell<usize>>) -> Ordering {
+        self.packet_cmp_with(rhs, CompareMode::default(), counter)
+    }
+
+    /// Same as `packet_cmp`, but with a different way to deal with numbers compared to lists.
+    #[allow(dead_code)]
+    fn packet_cmp_mode(&self, rhs: Self, mode: CompareMode) -> Ordering {
+        self.packet_cmp_with(rhs, mode, None)
+    }
+
+    fn packet_cmp_with(&self, rhs: Self, mode: CompareMode, counter: Option<&Cell<usize>>)
+        -> Ordering
+    {
         use PacketItem::*;
         let mut left_iter = self.iter();
         let mut right_iter = rhs.iter();
         loop {
-            let ordering = match (left_iter.next(), right_iter.next()) {
+            let items = (left_iter.next(), right_iter.next());
+            if let (Some(_), Some(_), Some(counter)) = (items.0, items.1, counter) {
+                counter.set(counter.get() + 1);
+            }
+            let ordering = match items {
                 (Some(Number(left)), Some(Number(right))) => left.cmp(&right),
                 (Some(List(left_list)), Some(List(right_list)))
-                    => left_list.packet_cmp(right_list),
+                    => left_list.packet_cmp_with(right_list, mode, counter),
+                (Some(List(_)), Some(Number(_))) | (Some(Number(_)), Some(List(_)))
+                    if mode == CompareMode::MismatchEqual => Ordering::Equal,
                 (Some(List(left_list)), Some(Number(right_number))) => {
                     let tmp_slice = &[FlatPacketItem::Number(right_number)];
-                    left_list.packet_cmp(PacketSlice(tmp_slice))
+                    left_list.packet_cmp_with(PacketSlice(tmp_slice), mode, counter)
                 },
                 (Some(Number(left_number)), Some(List(right_list))) => {
                     let tmp_slice = &[FlatPacketItem::Number(left_number)];
-                    PacketSlice(tmp_slice).packet_cmp(right_list)
+                    PacketSlice(tmp_slice).packet_cmp_with(right_list, mode, counter)
                 },
                 (None, None) => return Ordering::Equal,
                 (Some(_), None) => return Ordering::Greater,
@@ -195,35 +252,81 @@ impl<'a> Iterator for PacketIter<'a> {
     }
 }
 
+/// Compares two packets given in their string representation.
+fn cmp_str(left: &str, right: &str) -> Ordering {
+    Packet::parse(left).cmp(&Packet::parse(right))
+}
+
+
+/// A packet that has not been parsed yet. Orders just like `Packet`, so string representations of
+/// packets can be sorted directly.
+#[allow(dead_code)]
+#[derive(Debug, Copy, Clone)]
+struct PacketRef<'a>(&'a str);
+
+impl PartialEq for PacketRef<'_> {
+    fn eq(&self, rhs: &Self) -> bool {
+        self.cmp(rhs) == Ordering::Equal
+    }
+}
+
+impl Eq for PacketRef<'_> {}
+
+impl PartialOrd for PacketRef<'_> {
+    fn partial_cmp(&self, rhs: &Self) -> Option<Ordering> {
+        Some(self.cmp(rhs))
+    }
+}
+
+impl Ord for PacketRef<'_> {
+    fn cmp(&self, rhs: &Self) -> Ordering {
+        cmp_str(self.0, rhs.0)
+    }
+}
+
+
 fn is_in_order(left: &str, right: &str) -> bool {
-    let left_packet = Packet::parse(left);
-    let right_packet = Packet::parse(right);
-    match left_packet.cmp(&right_packet) {
+    match cmp_str(left, right) {
         Ordering::Less => true,
         Ordering::Greater => false,
         Ordering::Equal => panic!("I think this case is not even defined"),
     }
 }
 
-fn part_one(input: &str) -> usize {
+/// Lazily parses the input into pairs of packets.
+#[allow(dead_code)]
+fn pairs(input: &str) -> impl Iterator<Item = (Packet, Packet)> + '_ {
+    input.split("\n\n")
+        .map(|pair| pair.split_once('\n').unwrap())
+        .map(|(left, right)| (Packet::parse(left), Packet::parse(right)))
+}
+
+/// 1-based indices of all pairs that are in the right order.
+fn in_order_indices(input: &str) -> Vec<usize> {
     input.split("\n\n")
         .map(|pair| pair.split_once('\n').unwrap())
         .enumerate()
         .filter_map(|(index, (left, right))| is_in_order(left, right).then_some(index + 1))
-        .sum()
+        .collect()
 }
 
-fn part_two(input: &str) -> usize {
+fn part_one(input: &str) -> usize {
+    in_order_indices(input).iter().sum()
+}
+
+/// Sorts all packets along with the divider packets and multiplies the dividers' 1-based
+/// positions. Item comparisons performed while sorting are added to `comparisons`, if given.
+fn decoder_key(input: &str, comparisons: Option<&Cell<usize>>) -> usize {
     let mut packets = input.lines()
         .filter(|line| !line.is_empty())
-        .map(|line| Packet::parse(line))
+        .map(Packet::parse)
         .collect::<Vec<_>>();
 
     let divider_1 = Packet::parse("[[2]]");
     let divider_2 = Packet::parse("[[6]]");
     packets.push(divider_1.clone());
     packets.push(divider_2.clone());
-    packets.sort_unstable();
+    packets.sort_unstable_by(|a, b| a.slice().packet_cmp_counted(b.slice(), comparisons));
 
     let divider_1_pos = packets.binary_search(&divider_1).ok().unwrap();
     let divider_2_pos = packets.binary_search(&divider_2).ok().unwrap();
@@ -231,15 +334,44 @@ fn part_two(input: &str) -> usize {
     (divider_1_pos + 1) * (divider_2_pos + 1)
 }
 
+fn part_two(input: &str) -> usize {
+    decoder_key(input, None)
+}
+
+/// Same as `part_two`, but also returns the number of item comparisons performed while sorting.
+#[allow(dead_code)]
+fn part_two_with_stats(input: &str) -> (usize, usize) {
+    let comparisons = Cell::new(0);
+    let key = decoder_key(input, Some(&comparisons));
+    (key, comparisons.get())
+}
+
 
 static INPUT: &str = include_str!("inputs/day13.txt");
 
-pub fn run() {
-    let part1 = part_one(INPUT);
-    println!("Sum of indices of packets that are in right order: {part1}");
 
-    let part2 = part_two(INPUT);
-    println!("Decoder key: {part2}");
+pub struct Day13;
+
+impl Puzzle for Day13 {
+    fn input(&self) -> &'static str {
+        INPUT
+    }
+
+    fn part1(&self, input: &str) -> String {
+        part_one(input).to_string()
+    }
+
+    fn part2(&self, input: &str) -> String {
+        part_two(input).to_string()
+    }
+
+    fn run(&self) {
+        let part1 = part_one(INPUT);
+        println!("Sum of indices of packets that are in right order: {part1}");
+
+        let part2 = part_two(INPUT);
+        println!("Decoder key: {part2}");
+    }
 }
 
 
@@ -289,4 +421,130 @@ mod test {
         assert!(is_in_order("[[4,4],4,4]", "[[4,4],4,4,4]"));
         assert!(!is_in_order("[1,[2,[3,[4,[5,6,7]]]],8,9]","[1,[2,[3,[4,[5,6,0]]]],8,9]"));
     }
+
+    #[test]
+    fn compare_modes() {
+        let cmp = |left: &str, right: &str, mode| {
+            Packet::parse(left).slice().packet_cmp_mode(Packet::parse(right).slice(), mode)
+        };
+
+        // [2] vs 1 becomes [2] vs [1] with coercion
+        assert_eq!(cmp("[[2]]", "[1]", CompareMode::Coerce), Ordering::Greater);
+        assert_eq!(cmp("[[2]]", "[1]", CompareMode::MismatchEqual), Ordering::Equal);
+        assert_eq!(cmp("[[1],4]", "[2,3]", CompareMode::Coerce), Ordering::Less);
+        assert_eq!(cmp("[[1],4]", "[2,3]", CompareMode::MismatchEqual), Ordering::Greater);
+
+        // default mode agrees with the regular ordering
+        assert_eq!(cmp("[[1],[2,3,4]]", "[[1],4]", CompareMode::default()),
+            cmp_str("[[1],[2,3,4]]", "[[1],4]"));
+    }
+
+    #[test]
+    fn depth() {
+        assert!(Packet::parse("[]").depth() == 1);
+        assert!(Packet::parse("[1,2,3]").depth() == 1);
+        assert!(Packet::parse("[[[]]]").depth() == 3);
+        assert!(Packet::parse("[[1],[2,3,4]]").depth() == 2);
+        assert!(Packet::parse("[[[]],[]]").depth() == 3);
+        assert!(Packet::parse("[[],[],[[]]]").depth() == 3);
+        assert!(Packet::parse("[1,[2,[3,[4,[5,6,7]]]],8,9]").depth() == 5);
+    }
+
+    #[test]
+    fn pair_iterator() {
+        let input = "[1,1,3,1,1]
+                     [1,1,5,1,1]
+
+                     [[1],[2,3,4]]
+                     [[1],4]
+
+                     [9]
+                     [[8,7,6]]
+
+                     [[4,4],4,4]
+                     [[4,4],4,4,4]
+
+                     [7,7,7,7]
+                     [7,7,7]
+
+                     []
+                     [3]
+
+                     [[[]]]
+                     [[]]
+
+                     [1,[2,[3,[4,[5,6,7]]]],8,9]
+                     [1,[2,[3,[4,[5,6,0]]]],8,9]";
+        let in_order = pairs(input)
+            .map(|(left, right)| left < right)
+            .collect::<Vec<_>>();
+        assert!(in_order == [true, true, false, true, false, true, false, false]);
+
+        let index_sum = in_order.iter()
+            .enumerate()
+            .filter_map(|(index, ordered)| ordered.then_some(index + 1))
+            .sum::<usize>();
+        assert!(index_sum == 13);
+        assert!(part_one(input) == index_sum);
+        assert!(in_order_indices(input) == [1, 2, 4, 6]);
+    }
+
+    #[test]
+    fn sort_strings() {
+        let input = vec![
+            "[1,1,3,1,1]", "[1,1,5,1,1]", "[[1],[2,3,4]]", "[[1],4]", "[9]", "[[8,7,6]]",
+            "[[4,4],4,4]", "[[4,4],4,4,4]", "[7,7,7,7]", "[7,7,7]", "[]", "[3]", "[[[]]]", "[[]]",
+            "[1,[2,[3,[4,[5,6,7]]]],8,9]", "[1,[2,[3,[4,[5,6,0]]]],8,9]",
+        ];
+
+        let mut expected = input.iter().map(|s| Packet::parse(s)).collect::<Vec<_>>();
+        expected.sort();
+
+        let mut sorted = input.clone();
+        sorted.sort_by(|a, b| cmp_str(a, b));
+        assert!(sorted.iter().map(|s| Packet::parse(s)).collect::<Vec<_>>() == expected);
+
+        let mut sorted = input.iter().copied().map(PacketRef).collect::<Vec<_>>();
+        sorted.sort();
+        assert!(sorted.iter().map(|p| Packet::parse(p.0)).collect::<Vec<_>>() == expected);
+        assert!(sorted[0] == PacketRef("[]"));
+    }
+
+    #[test]
+    fn comparison_stats() {
+        let input = "[1,1,3,1,1]
+                     [1,1,5,1,1]
+
+                     [[1],[2,3,4]]
+                     [[1],4]
+
+                     [9]
+                     [[8,7,6]]
+
+                     [[4,4],4,4]
+                     [[4,4],4,4,4]
+
+                     [7,7,7,7]
+                     [7,7,7]
+
+                     []
+                     [3]
+
+                     [[[]]]
+                     [[]]
+
+                     [1,[2,[3,[4,[5,6,7]]]],8,9]
+                     [1,[2,[3,[4,[5,6,0]]]],8,9]";
+        let (key, comparisons) = part_two_with_stats(input);
+        assert!(key == 140);
+        assert!(key == part_two(input));
+        assert!(comparisons > 0);
+
+        let counter = Cell::new(0);
+        let left = Packet::parse("[[1],[2,3,4]]");
+        let right = Packet::parse("[[1],4]");
+        assert!(left.slice().packet_cmp_counted(right.slice(), Some(&counter)) == Ordering::Less);
+        // the outer lists, [1] vs [1], 1 vs 1, [2,3,4] vs 4, 2 vs 4
+        assert!(counter.get() == 5);
+    }
 }
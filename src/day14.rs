@@ -1,6 +1,10 @@
 
+use crate::common::Puzzle;
+
 use ndarray::{s, Array2};
 
+use std::collections::{HashMap, VecDeque};
+
 type LocalCoord = usize;
 type LocalPoint = cgmath::Vector2<LocalCoord>;
 
@@ -43,7 +47,7 @@ impl GlobalLine {
     /// Walks along this line and posts all encountered coordinates to the passed closure.
     fn walk<F>(&self, mut f: F)
     where
-        F: FnMut(GlobalPoint) -> (),
+        F: FnMut(GlobalPoint),
     {
         let step = if self.start.x == self.end.x {
             GlobalPoint::new(0, 1)
@@ -113,6 +117,13 @@ enum StepResult {
 }
 
 
+#[derive(Debug, PartialEq, Eq)]
+enum MapParseError {
+    NegativeCoordinate(GlobalPoint),
+    CoordinateTooLarge(GlobalPoint),
+}
+
+
 #[derive(Debug, Clone)]
 struct Map {
     origin: GlobalPoint,
@@ -129,13 +140,29 @@ impl Map {
     const GROWTH_STEP_LEFT: GlobalCoord = -(Self::GROWTH_STEP as GlobalCoord);
     const GROWTH_STEP_RIGHT: GlobalCoord = Self::GROWTH_STEP as GlobalCoord;
 
+    /// Upper bound for wall coordinates. Anything beyond is probably a typo and would blow up the
+    /// size of the tile array.
+    const MAX_COORD: GlobalCoord = 100_000;
+
     fn parse(input: &str) -> Self {
+        Self::try_parse(input).unwrap()
+    }
+
+    fn try_parse(input: &str) -> Result<Self, MapParseError> {
         // iterator over iterator of wall segments. ideally, we'd flatten that here, but for some
         // reason, Flatten is not Clone.
         let walls = input.lines()
             .filter(|line| !line.is_empty())
             .map(|line| path_segments(line.trim()));
 
+        for point in walls.clone().flatten().flat_map(|line| [line.start, line.end]) {
+            if point.x < 0 || point.y < 0 {
+                return Err(MapParseError::NegativeCoordinate(point));
+            } else if point.x > Self::MAX_COORD || point.y > Self::MAX_COORD {
+                return Err(MapParseError::CoordinateTooLarge(point));
+            }
+        }
+
         // internally, we use a local coordinate system in which the top-left of the map is always
         // at (0,0) and no negative coordinates can occur. to transform the global coordinates, we
         // need to find the minimum and maximum global coordinates.
@@ -162,17 +189,61 @@ impl Map {
             });
         }
 
-        Self {
+        Ok(Self {
             origin,
             tiles,
             has_floor: false,
-        }
+        })
     }
 
     fn local_sand_source(&self) -> LocalPoint {
         (Self::SAND_SOURCE - self.origin).cast::<LocalCoord>().unwrap()
     }
 
+    /// The minimum and maximum global coordinates of the simulated area, both inclusive. This
+    /// includes the empty row above the floor, but not the floor itself.
+    #[allow(dead_code)]
+    fn bounds(&self) -> (GlobalPoint, GlobalPoint) {
+        let max = LocalPoint::new(self.tiles.ncols() - 1, self.tiles.nrows() - 1);
+        (self.origin, self.to_global(max))
+    }
+
+    /// Iterates over all rock tiles, in global coordinates.
+    #[allow(dead_code)]
+    fn rock_cells(&self) -> impl Iterator<Item = GlobalPoint> + '_ {
+        self.tiles.indexed_iter()
+            .filter(|(_, tile)| **tile == Tile::Rock)
+            .map(|((y, x), _)| LocalPoint::new(x, y).cast::<GlobalCoord>().unwrap() + self.origin)
+    }
+
+    /// The y coordinate of the topmost sand unit in each column, starting with the column at the
+    /// map's origin. `None` for columns without sand.
+    #[allow(dead_code)]
+    fn sand_surface(&self) -> Vec<Option<GlobalCoord>> {
+        self.tiles.columns().into_iter()
+            .map(|column| column.iter()
+                .position(|tile| *tile == Tile::Sand)
+                .map(|y| y as GlobalCoord + self.origin.y))
+            .collect()
+    }
+
+    /// Renders the map like `Display` does, but with every cell blown up to `scale` by `scale`
+    /// characters.
+    #[allow(dead_code)]
+    fn render_scaled(&self, scale: usize) -> String {
+        let mut out = String::new();
+        for line in self.to_string().lines() {
+            let scaled_line = line.chars()
+                .flat_map(|c| std::iter::repeat_n(c, scale))
+                .collect::<String>();
+            for _ in 0..scale {
+                out.push_str(&scaled_line);
+                out.push('\n');
+            }
+        }
+        out
+    }
+
     fn set_has_floor(&mut self, floor: bool) {
         self.has_floor = floor;
     }
@@ -185,7 +256,7 @@ impl Map {
 
         // create new, grown array
         let old_cols = self.tiles.ncols();
-        let units_abs = units.abs() as usize;
+        let units_abs = units.unsigned_abs();
         let new_cols = old_cols + units_abs;
         let mut new_tiles = Array2::from_elem((self.tiles.nrows(), new_cols), Tile::Air);
 
@@ -209,13 +280,32 @@ impl Map {
     ///
     /// Oh boy, part 2 made this into a nice italian pasta dish.
     fn step(&mut self) -> StepResult {
+        self.step_traced(|_| ())
+    }
 
+    /// Whether sand has piled up all the way to the source, so no more sand can be placed.
+    fn is_complete(&self) -> bool {
         let source = self.local_sand_source();
-        if self.tiles[[source.y, source.x]].is_solid() {
+        self.tiles[[source.y, source.x]].is_solid()
+    }
+
+    fn to_global(&self, p: LocalPoint) -> GlobalPoint {
+        p.cast::<GlobalCoord>().unwrap() + self.origin
+    }
+
+    /// Same as `step`, but calls `visit` for every tile the sand passes, including the source and
+    /// the tile it comes to rest on.
+    fn step_traced<F>(&mut self, mut visit: F) -> StepResult
+    where
+        F: FnMut(GlobalPoint),
+    {
+        if self.is_complete() {
             return StepResult::SourceBlocked;
         }
+        let source = self.local_sand_source();
 
         let mut sand = source;
+        visit(self.to_global(sand));
 
         let one_above_floor = self.tiles.nrows() - 1;
 
@@ -223,15 +313,23 @@ impl Map {
             // slice the map so we only look at the tiles directly below the sand
             let vert_slice = self.tiles.slice(s![(sand.y + 1).., sand.x]);
             if let Some(fall_distance) = vert_slice.iter().position(|tile| tile.is_solid()) {
-                sand.y += fall_distance;
+                for _ in 0..fall_distance {
+                    sand.y += 1;
+                    visit(self.to_global(sand));
+                }
             } else {
                 // no explicit solid under sand. sand falls into void or hits the infinite floor
                 if self.has_floor {
-                    sand.y = one_above_floor;
+                    while sand.y < one_above_floor {
+                        sand.y += 1;
+                        visit(self.to_global(sand));
+                    }
                     self.tiles[[sand.y, sand.x]] = Tile::Sand;
-                    let global_sand = sand.cast::<GlobalCoord>().unwrap() + self.origin;
-                    return StepResult::CameToRest(global_sand);
+                    return StepResult::CameToRest(self.to_global(sand));
                 } else {
+                    for y in (sand.y + 1)..self.tiles.nrows() {
+                        visit(self.to_global(LocalPoint::new(sand.x, y)));
+                    }
                     return StepResult::FellIntoVoid;
                 }
             }
@@ -244,7 +342,10 @@ impl Map {
                     // sand will fall onto floor just outside the left bounds. need to grow.
                     self.grow(Self::GROWTH_STEP_LEFT);
                     sand.x = sand.x + Self::GROWTH_STEP - 1;
-                    sand.y = one_above_floor;
+                    while sand.y < one_above_floor {
+                        sand.y += 1;
+                        visit(self.to_global(sand));
+                    }
                 } else {
                     // sand will unconditionally fall into the void out of the left bounds
                     return StepResult::FellIntoVoid;
@@ -253,6 +354,7 @@ impl Map {
                 // can move into left space
                 sand.x -= 1;
                 sand.y += 1;
+                visit(self.to_global(sand));
                 continue;
             } else if sand.x + 1 >= self.tiles.ncols() {
                 // at right border
@@ -260,7 +362,10 @@ impl Map {
                     // sand will fall onto floor just outside the right bounds. need to grow.
                     self.grow(Self::GROWTH_STEP_RIGHT);
                     sand.x += 1;
-                    sand.y = one_above_floor;
+                    while sand.y < one_above_floor {
+                        sand.y += 1;
+                        visit(self.to_global(sand));
+                    }
                 } else {
                     // sand will unconditionally fall into the void out of the right bounds
                     return StepResult::FellIntoVoid;
@@ -269,13 +374,13 @@ impl Map {
                 // can move into right space
                 sand.x += 1;
                 sand.y += 1;
+                visit(self.to_global(sand));
                 continue;
             }
 
             // if we end up here, sand comes to rest.
             self.tiles[[sand.y, sand.x]] = Tile::Sand;
-            let global_sand = sand.cast::<GlobalCoord>().unwrap() + self.origin;
-            return StepResult::CameToRest(global_sand);
+            return StepResult::CameToRest(self.to_global(sand));
         }
     }
 }
@@ -307,7 +412,16 @@ impl std::fmt::Display for Map {
 }
 
 
-fn count_sand_units(mut map: Map) -> usize {
+fn count_sand_units(map: Map) -> usize {
+    count_sand_units_observed(map, |_| ())
+}
+
+/// Like `count_sand_units`, but calls `on_rest` with the position of every unit of sand that comes
+/// to rest.
+fn count_sand_units_observed<F>(mut map: Map, mut on_rest: F) -> usize
+where
+    F: FnMut(GlobalPoint),
+{
     let mut sand_units_placed = 0;
     //println!("========================");
     //println!("Initial state");
@@ -317,7 +431,8 @@ fn count_sand_units(mut map: Map) -> usize {
         //println!("------------------------");
         //println!("Result: {result:?}");
         //println!("{}", &map);
-        if let StepResult::CameToRest(_) = result {
+        if let StepResult::CameToRest(point) = result {
+            on_rest(point);
             sand_units_placed += 1;
         } else {
             break;
@@ -326,19 +441,204 @@ fn count_sand_units(mut map: Map) -> usize {
     sand_units_placed
 }
 
+/// Counts the units of sand that pass over the given cell before coming to rest somewhere else or
+/// falling into the void.
+#[allow(dead_code)]
+fn count_flow_through(mut map: Map, cell: GlobalPoint) -> usize {
+    let mut flow = 0;
+    loop {
+        let mut crossed = false;
+        let result = map.step_traced(|point| crossed |= point == cell);
+        match result {
+            StepResult::CameToRest(point) => {
+                if crossed && point != cell {
+                    flow += 1;
+                }
+            },
+            StepResult::FellIntoVoid => {
+                if crossed {
+                    flow += 1;
+                }
+                break;
+            },
+            StepResult::SourceBlocked => break,
+        }
+    }
+    flow
+}
+
+/// Depth-first sand fill below the given local point. Returns false as soon as a path into the
+/// void has been found.
+fn fill_sand(tiles: &mut Array2<Tile>, x: LocalCoord, y: LocalCoord, count: &mut usize) -> bool {
+    if tiles[[y, x]].is_solid() {
+        return true;
+    }
+
+    if y + 1 >= tiles.nrows() {
+        return false;
+    }
+
+    // same order in which a grain of sand tries to move
+    for next_x in [Some(x), x.checked_sub(1), Some(x + 1)] {
+        match next_x {
+            Some(next_x) if next_x < tiles.ncols() => if !fill_sand(tiles, next_x, y + 1, count) {
+                return false;
+            },
+            _ => return false, // falls out of the left or right bounds
+        }
+    }
+
+    // everything below is solid. sand comes to rest here.
+    tiles[[y, x]] = Tile::Sand;
+    *count += 1;
+    true
+}
+
+/// Computes the same as `count_sand_units` for a map without floor, but without simulating each
+/// unit of sand separately.
+///
+/// Since sand always tries to move down, then down-left, then down-right, a depth-first fill from
+/// the source in that same order comes to rest in the same tiles as the simulated sand would. The
+/// first path into the void found this way is the one the first lost unit of sand takes.
+#[allow(dead_code)]
+fn count_sand_part_one_fast(map: &Map) -> usize {
+    let mut tiles = map.tiles.clone();
+    let source = map.local_sand_source();
+    let mut count = 0;
+    fill_sand(&mut tiles, source.x, source.y, &mut count);
+    count
+}
+
+/// Finds the minimal number of rock tiles that need to be added so no sand falls into the void
+/// (without a floor). The source itself may not be blocked.
+///
+/// If no sand is lost, all tiles the sand can reach will eventually be filled. So it suffices to
+/// cut all paths from the source into the void, which makes this a minimum vertex cut problem.
+/// Solved by splitting each air tile into an in and an out node connected with capacity one and
+/// running Edmonds-Karp. Since the source only has three successors, there are at most three
+/// augmenting paths.
+#[allow(dead_code)]
+fn min_blocking_walls(map: &Map) -> usize {
+    const INFINITE: usize = usize::MAX;
+
+    let tiles = &map.tiles;
+    let (rows, cols) = tiles.dim();
+    let source = map.local_sand_source();
+    if tiles[[source.y, source.x]].is_solid() {
+        return 0;
+    }
+
+    // node layout: in and out node for every tile, plus a single node representing the void
+    let node_in = |y: LocalCoord, x: LocalCoord| 2 * (y * cols + x);
+    let node_out = |y: LocalCoord, x: LocalCoord| 2 * (y * cols + x) + 1;
+    let void = 2 * rows * cols;
+
+    let mut capacity: HashMap<(usize, usize), usize> = HashMap::new();
+    let mut adjacency = vec![Vec::new(); void + 1];
+    let mut add_edge = |from: usize, to: usize, cap: usize| {
+        *capacity.entry((from, to)).or_insert(0) += cap;
+        capacity.entry((to, from)).or_insert(0);
+        adjacency[from].push(to);
+        adjacency[to].push(from);
+    };
+
+    for ((y, x), tile) in tiles.indexed_iter() {
+        if tile.is_solid() {
+            continue;
+        }
+
+        let through = if LocalPoint::new(x, y) == source { INFINITE } else { 1 };
+        add_edge(node_in(y, x), node_out(y, x), through);
+
+        if y + 1 >= rows {
+            // falls out the bottom
+            add_edge(node_out(y, x), void, INFINITE);
+            continue;
+        }
+
+        for next_x in [x.checked_sub(1), Some(x), Some(x + 1)] {
+            match next_x {
+                Some(next_x) if next_x < cols => if !tiles[[y + 1, next_x]].is_solid() {
+                    add_edge(node_out(y, x), node_in(y + 1, next_x), INFINITE);
+                },
+                // falls out the left or right bounds. the tile just outside could be blocked, so
+                // this is worth one wall.
+                _ => add_edge(node_out(y, x), void, 1),
+            }
+        }
+    }
+
+    let start = node_in(source.y, source.x);
+    let mut predecessor = vec![None; void + 1];
+    let mut queue = VecDeque::new();
+    let mut flow = 0;
+    loop {
+        // find augmenting path using BFS
+        predecessor.fill(None);
+        predecessor[start] = Some(start);
+        queue.clear();
+        queue.push_back(start);
+        while let Some(node) = queue.pop_front() {
+            if node == void {
+                break;
+            }
+            for &next in &adjacency[node] {
+                if predecessor[next].is_none() && capacity[&(node, next)] > 0 {
+                    predecessor[next] = Some(node);
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        if predecessor[void].is_none() {
+            return flow;
+        }
+
+        // all paths pass through at least one edge of capacity one, so we can always push exactly
+        // one unit of flow.
+        let mut node = void;
+        while node != start {
+            let prev = predecessor[node].unwrap();
+            *capacity.get_mut(&(prev, node)).unwrap() -= 1;
+            *capacity.get_mut(&(node, prev)).unwrap() += 1;
+            node = prev;
+        }
+        flow += 1;
+    }
+}
+
 
 
 
 static INPUT: &str = include_str!("inputs/day14.txt");
 
-pub fn run() {
-    let mut map = Map::parse(INPUT);
-    let part1 = count_sand_units(map.clone());
-    println!("Sand units that came to rest: {part1}");
 
-    map.set_has_floor(true);
-    let part2 = count_sand_units(map);
-    println!("Sand units that came to rest with infinite floor: {part2}");
+pub struct Day14;
+
+impl Puzzle for Day14 {
+    fn input(&self) -> &'static str {
+        INPUT
+    }
+
+    fn part1(&self, input: &str) -> String {
+        count_sand_units(Map::parse(input)).to_string()
+    }
+
+    fn part2(&self, input: &str) -> String {
+        let mut map = Map::parse(input);
+        map.set_has_floor(true);
+        count_sand_units(map).to_string()
+    }
+
+    fn run(&self) {
+        let mut map = Map::parse(INPUT);
+        let part1 = count_sand_units(map.clone());
+        println!("Sand units that came to rest: {part1}");
+
+        map.set_has_floor(true);
+        let part2 = count_sand_units(map);
+        println!("Sand units that came to rest with infinite floor: {part2}");
+    }
 }
 
 
@@ -365,6 +665,158 @@ mod test {
 
         let mut map = Map::parse(input);
         map.set_has_floor(true);
-        assert_eq!(count_sand_units(map), 93);
+        assert!(!map.is_complete());
+        assert_eq!(count_sand_units(map.clone()), 93);
+        while let CameToRest(_) = map.step() {}
+        assert!(map.is_complete());
+        assert_eq!(map.step(), SourceBlocked);
+
+        // without a floor, sand falls into the void long before it reaches the source
+        let mut map = Map::parse(input);
+        while let CameToRest(_) = map.step() {}
+        assert!(!map.is_complete());
+    }
+
+    #[test]
+    fn surface() {
+        let input = "498,4 -> 498,6 -> 496,6
+                     503,4 -> 502,4 -> 502,9 -> 494,9";
+        let mut map = Map::parse(input);
+        assert!(map.sand_surface().iter().all(Option::is_none));
+
+        while let StepResult::CameToRest(_) = map.step() {}
+        let surface = map.sand_surface();
+        assert_eq!(surface, &[None, Some(8), None, Some(5), Some(7), Some(3), Some(2), Some(3), None,
+            None]);
+
+        // the column below the source
+        let source_column = (Map::SAND_SOURCE.x - map.origin.x) as usize;
+        assert_eq!(surface[source_column], Some(2));
+    }
+
+    #[test]
+    fn bounding_box() {
+        let input = "498,4 -> 498,6 -> 496,6
+                     503,4 -> 502,4 -> 502,9 -> 494,9";
+        let mut map = Map::parse(input);
+        let (min, max) = map.bounds();
+        assert_eq!(min, map.origin);
+        assert_eq!(min, GlobalPoint::new(494, 0));
+        // one extra row for the floor to sit below
+        assert_eq!(max, GlobalPoint::new(503, 10));
+        assert_eq!((max - min).x as usize + 1, map.tiles.ncols());
+
+        map.grow(-3);
+        map.grow(2);
+        assert_eq!(map.bounds(), (GlobalPoint::new(491, 0), GlobalPoint::new(505, 10)));
+    }
+
+    #[test]
+    fn scaled_render() {
+        let input = "498,4 -> 498,6 -> 496,6
+                     503,4 -> 502,4 -> 502,9 -> 494,9";
+        let map = Map::parse(input);
+        let plain = map.to_string();
+        let scaled = map.render_scaled(2);
+        assert_eq!(scaled.lines().count(), plain.lines().count() * 2);
+        assert!(scaled.lines().all(|l| l.chars().count() == map.tiles.ncols() * 2));
+        assert_eq!(map.render_scaled(1), plain);
+
+        // the first rock row is y = 4
+        let rock_row = scaled.lines().nth(8).unwrap();
+        assert_eq!(rock_row, "        ██      ████");
+        assert_eq!(scaled.lines().nth(9).unwrap(), rock_row);
+    }
+
+    #[test]
+    fn invalid_coordinates() {
+        let result = Map::try_parse("498,4 -> 498,-6 -> 496,-6");
+        assert_eq!(result.err(), Some(MapParseError::NegativeCoordinate(GlobalPoint::new(498, -6))));
+
+        let result = Map::try_parse("498,4 -> 498,6\n-3,0 -> 5,0");
+        assert_eq!(result.err(), Some(MapParseError::NegativeCoordinate(GlobalPoint::new(-3, 0))));
+
+        let result = Map::try_parse("498,4 -> 498,6000000");
+        assert_eq!(result.err(),
+            Some(MapParseError::CoordinateTooLarge(GlobalPoint::new(498, 6000000))));
+    }
+
+    #[test]
+    fn blocking_walls() {
+        // a cup catches all the sand
+        let map = Map::parse("498,1 -> 498,3 -> 502,3 -> 502,1");
+        assert_eq!(min_blocking_walls(&map), 0);
+        assert_eq!(count_sand_part_one_fast(&map), 7);
+
+        // a small shelf lets sand slide off to both sides
+        let map = Map::parse("499,2 -> 501,2");
+        assert_eq!(min_blocking_walls(&map), 2);
+
+        // a narrow shaft below the source can be plugged with a single rock
+        let map = Map::parse("499,1 -> 499,2
+                              501,1 -> 501,2");
+        assert_eq!(min_blocking_walls(&map), 1);
+
+        // nothing below the source
+        let map = Map::parse("490,5 -> 491,5");
+        assert_eq!(min_blocking_walls(&map), 3);
+    }
+
+    #[test]
+    fn rocks() {
+        let input = "498,4 -> 498,6 -> 496,6
+                     503,4 -> 502,4 -> 502,9 -> 494,9";
+        let map = Map::parse(input);
+        let rocks = map.rock_cells().collect::<Vec<_>>();
+        assert_eq!(rocks.len(), 5 + 15);
+        assert!(rocks.contains(&GlobalPoint::new(498, 4)));
+        assert!(rocks.contains(&GlobalPoint::new(496, 6)));
+        assert!(rocks.contains(&GlobalPoint::new(494, 9)));
+        assert!(!rocks.contains(&GlobalPoint::new(500, 0)));
+    }
+
+    #[test]
+    fn observed() {
+        let input = "498,4 -> 498,6 -> 496,6
+                     503,4 -> 502,4 -> 502,9 -> 494,9";
+        let map = Map::parse(input);
+        let mut resting = Vec::new();
+        let count = count_sand_units_observed(map, |point| resting.push(point));
+        assert_eq!(count, 24);
+        assert_eq!(resting.len(), 24);
+        assert_eq!(resting[0], GlobalPoint::new(500, 8));
+        assert_eq!(resting[23], GlobalPoint::new(495, 8));
+    }
+
+    #[test]
+    fn flow_through() {
+        let input = "498,4 -> 498,6 -> 496,6
+                     503,4 -> 502,4 -> 502,9 -> 494,9";
+        let map = Map::parse(input);
+
+        // every unit, including the one falling into the void, passes just below the source
+        assert_eq!(count_flow_through(map.clone(), GlobalPoint::new(500, 1)), 24 + 1);
+        assert_eq!(count_flow_through(map.clone(), Map::SAND_SOURCE), 24 + 1);
+        // the first unit comes to rest here, blocking the cell for all that follow
+        assert_eq!(count_flow_through(map.clone(), GlobalPoint::new(500, 8)), 0);
+        assert_eq!(count_flow_through(map.clone(), GlobalPoint::new(0, 0)), 0);
+
+        let mut visited = Vec::new();
+        let mut map = map;
+        let result = map.step_traced(|p| visited.push(p));
+        assert_eq!(result, StepResult::CameToRest(GlobalPoint::new(500, 8)));
+        assert_eq!(visited, (0..=8).map(|y| GlobalPoint::new(500, y)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn fast_part_one() {
+        let input = "498,4 -> 498,6 -> 496,6
+                     503,4 -> 502,4 -> 502,9 -> 494,9";
+        let map = Map::parse(input);
+        assert_eq!(count_sand_part_one_fast(&map), 24);
+        assert_eq!(count_sand_part_one_fast(&map), count_sand_units(map));
+
+        let map = Map::parse(INPUT);
+        assert_eq!(count_sand_part_one_fast(&map), count_sand_units(map));
     }
 }
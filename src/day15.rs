@@ -1,29 +1,45 @@
 
+use crate::common::{manhattan, Puzzle};
+
+use std::ops::RangeInclusive;
+
+
 type Coord = i64;
 type Point = cgmath::Vector2<Coord>;
 
 
-fn point_from_coords(s: &str) -> Point {
+#[derive(Debug, PartialEq, Eq)]
+enum SensorParseError {
+    MissingPart,
+    BadCoordinate,
+    #[allow(dead_code)]
+    UnknownDimension(String),
+    #[allow(dead_code)]
+    Io(std::io::ErrorKind),
+}
+
+fn point_from_coords(s: &str) -> Result<Point, SensorParseError> {
     let mut x = None;
     let mut y = None;
-    let items = s.split(',')
-        .map(|item| item.trim().split_once('=').unwrap());
-    for (dim, value) in items{
+    for item in s.split(',') {
+        let (dim, value) = item.trim().split_once('=').ok_or(SensorParseError::MissingPart)?;
+        let value = value.parse().map_err(|_| SensorParseError::BadCoordinate)?;
         match dim {
-            "x" => x = Some(value.parse().unwrap()),
-            "y" => y = Some(value.parse().unwrap()),
-            _ => panic!("Unknown dimension {dim}"),
+            "x" => x = Some(value),
+            "y" => y = Some(value),
+            _ => return Err(SensorParseError::UnknownDimension(dim.to_string())),
         }
     }
 
-    Point::new(x.unwrap(), y.unwrap())
+    Ok(Point::new(
+        x.ok_or(SensorParseError::MissingPart)?,
+        y.ok_or(SensorParseError::MissingPart)?,
+    ))
 }
 
 fn manhattan_distance(a: Point, b: Point) -> Coord {
     // why does cgmatch not have this?
-    let diff = a - b;
-    let arr: [Coord; 2] = diff.into();
-    arr.iter().map(|c| c.abs()).sum()
+    manhattan(a.into(), b.into()) as Coord
 }
 
 
@@ -67,21 +83,40 @@ impl CoordRange {
 
 struct Sensor {
     position: Point,
-    _beacon: Point,
+    beacon: Point,
     range: Coord,
 }
 
 impl Sensor {
     fn parse(s: &str) -> Sensor {
-        let (sensor, beacon) = s.trim().split_once(':').unwrap();
-        let sensor_coords = sensor.strip_prefix("Sensor at ").unwrap();
-        let beacon_coords = beacon.strip_prefix(" closest beacon is at ").unwrap();
-        let sensor = point_from_coords(sensor_coords);
-        let beacon = point_from_coords(beacon_coords);
-        Self {
+        Self::try_parse(s).unwrap()
+    }
+
+    fn try_parse(s: &str) -> Result<Sensor, SensorParseError> {
+        let (sensor, beacon) = s.trim().split_once(':').ok_or(SensorParseError::MissingPart)?;
+        let sensor_coords = sensor.strip_prefix("Sensor at ")
+            .ok_or(SensorParseError::MissingPart)?;
+        let beacon_coords = beacon.strip_prefix(" closest beacon is at ")
+            .ok_or(SensorParseError::MissingPart)?;
+        let sensor = point_from_coords(sensor_coords)?;
+        let beacon = point_from_coords(beacon_coords)?;
+        Ok(Self {
             position: sensor,
-            _beacon: beacon,
+            beacon,
             range: manhattan_distance(sensor, beacon),
+        })
+    }
+
+    /// The x coordinates in row y that are within range of this sensor, if any.
+    fn row_range(&self, y: Coord) -> Option<CoordRange> {
+        // this utilized the rectangular shape of the L1 norm:
+        let y_diff = (self.position.y - y).abs();
+        if y_diff <= self.range {
+            let start = self.position.x - self.range + y_diff;
+            let end = self.position.x + self.range - y_diff;
+            Some(CoordRange::new(start, end))
+        } else {
+            None
         }
     }
 }
@@ -94,13 +129,162 @@ struct Map {
 impl Map {
     fn parse(s: &str) -> Self {
         let sensors = s.lines()
-            .map(|line| Sensor::parse(line))
+            .map(Sensor::parse)
             .collect::<Vec<_>>();
 
         Self {
             sensors,
         }
     }
+
+    /// Same as `parse`, but reads the sensors line by line. Blank lines are skipped.
+    #[allow(dead_code)]
+    fn from_reader<R: std::io::BufRead>(reader: R) -> Result<Map, SensorParseError> {
+        let mut sensors = Vec::new();
+        for line in reader.lines() {
+            let line = line.map_err(|e| SensorParseError::Io(e.kind()))?;
+            if !line.trim().is_empty() {
+                sensors.push(Sensor::try_parse(&line)?);
+            }
+        }
+        Ok(Self {
+            sensors,
+        })
+    }
+
+    /// Sorted x coordinates of all distinct beacons in row y.
+    fn beacons_in_row(&self, y: Coord) -> Vec<Coord> {
+        let mut xs = self.sensors.iter()
+            .filter(|s| s.beacon.y == y)
+            .map(|s| s.beacon.x)
+            .collect::<Vec<_>>();
+        xs.sort_unstable();
+        xs.dedup();
+        xs
+    }
+
+    /// Lists the sensors covering row y by index, along with the range each one covers on its own.
+    #[allow(dead_code)]
+    fn row_contributions(&self, y: Coord) -> Vec<(usize, CoordRange)> {
+        self.sensors.iter()
+            .enumerate()
+            .filter_map(|(index, s)| s.row_range(y).map(|range| (index, range)))
+            .collect()
+    }
+
+    /// Checks whether the point is within range of any sensor.
+    #[allow(dead_code)]
+    fn is_covered(&self, p: Point) -> bool {
+        self.sensors.iter().any(|s| manhattan_distance(s.position, p) <= s.range)
+    }
+
+    /// Counts the cells within the given bounds that are in range of at least one sensor.
+    #[allow(dead_code)]
+    fn total_covered_area(&self, bounds: (RangeInclusive<Coord>, RangeInclusive<Coord>)) -> usize {
+        let (x_range, y_range) = bounds;
+        let mut finder = BeaconFinder::new(self);
+        let mut area = 0;
+        for y in y_range {
+            finder.collect_ranges(y);
+            area += finder.joint_ranges.iter()
+                .map(|range| {
+                    let start = range.start.max(*x_range.start());
+                    let end = range.end.min(*x_range.end());
+                    (end - start + 1).max(0)
+                })
+                .sum::<Coord>();
+        }
+        area.try_into().unwrap()
+    }
+
+    /// Finds the single uncovered position within `0..=max` in both dimensions without scanning
+    /// any rows.
+    ///
+    /// In coordinates rotated by 45°, u = x + y and v = x - y, the L1 ball around a sensor becomes
+    /// the axis-aligned square |u - u_s| ≤ r, |v - v_s| ≤ r. Since the hole is unique, it must be
+    /// squeezed between sensors from all four sides, so it lies on a line u = const (and v = const)
+    /// that runs just outside of one sensor's square and just outside of another's from the
+    /// opposite side. Intersecting all such lines gives only a handful of candidates. Mapping back
+    /// is x = (u + v) / 2, y = (u - v) / 2, which requires u and v to have the same parity.
+    ///
+    /// Holes that are only bounded by the edges of the search area, e.g. in a corner, don't lie on
+    /// such a pair of lines. If no candidate turns up, this falls back to scanning the rows.
+    #[allow(dead_code)]
+    fn find_beacon_diagonal(&self, max: Coord) -> Point {
+        let rotated = self.sensors.iter()
+            .map(|s| (s.position.x + s.position.y, s.position.x - s.position.y, s.range))
+            .collect::<Vec<_>>();
+
+        // lines running between two sensors that leave a gap of exactly one cell
+        let mut u_lines = Vec::new();
+        let mut v_lines = Vec::new();
+        for &(u_a, v_a, r_a) in &rotated {
+            for &(u_b, v_b, r_b) in &rotated {
+                if u_a + r_a + 2 == u_b - r_b {
+                    u_lines.push(u_a + r_a + 1);
+                }
+                if v_a + r_a + 2 == v_b - r_b {
+                    v_lines.push(v_a + r_a + 1);
+                }
+            }
+        }
+
+        let area = 0..=max;
+        u_lines.iter()
+            .flat_map(|&u| v_lines.iter().map(move |&v| (u, v)))
+            .filter(|(u, v)| (u - v) % 2 == 0)
+            .map(|(u, v)| Point::new((u + v) / 2, (u - v) / 2))
+            .find(|p| area.contains(&p.x) && area.contains(&p.y) && !self.is_covered(*p))
+            .unwrap_or_else(|| BeaconFinder::new(self).find_beacon(max))
+    }
+
+    /// Finds the largest axis-aligned rectangle within `0..=max` in both dimensions that is not
+    /// covered by any sensor. Returns its top left and bottom right corners, both inclusive.
+    ///
+    /// This sweeps over every row, keeping track of how far each column has been uncovered
+    /// upwards, and then looks for the largest rectangle in that histogram. So it's quadratic in
+    /// `max` and not fit for the real puzzle's dimensions.
+    #[allow(dead_code)]
+    fn largest_uncovered_rect(&self, max: Coord) -> Option<(Point, Point)> {
+        let width = usize::try_from(max).unwrap() + 1;
+        let mut finder = BeaconFinder::new(self);
+        let mut heights = vec![0; width];
+        let mut stack: Vec<(usize, Coord)> = Vec::new();
+        let mut best = None;
+        let mut best_area = 0;
+        for y in 0..=max {
+            finder.collect_ranges(y);
+            for (x, height) in heights.iter_mut().enumerate() {
+                let covered = finder.joint_ranges.iter()
+                    .any(|range| (range.start..=range.end).contains(&(x as Coord)));
+                *height = if covered { 0 } else { *height + 1 };
+            }
+
+            // the trailing zero flushes whatever is left on the stack
+            stack.clear();
+            for x in 0..=width {
+                let height = heights.get(x).copied().unwrap_or(0);
+                let mut start = x;
+                while let Some(&(bar_start, bar_height)) = stack.last() {
+                    if bar_height < height {
+                        break;
+                    }
+                    stack.pop();
+                    let area = bar_height * (x - bar_start) as Coord;
+                    if area > best_area {
+                        best_area = area;
+                        best = Some((Point::new(bar_start as Coord, y - bar_height + 1),
+                            Point::new(x as Coord - 1, y)));
+                    }
+                    start = bar_start;
+                }
+                if height > 0 {
+                    stack.push((start, height));
+                }
+            }
+        }
+        best
+    }
 }
 
 
@@ -125,18 +309,7 @@ impl<'a> BeaconFinder<'a> {
         self.ranges.clear();
         self.joint_ranges.clear();
 
-        // this utilized the rectangular shape of the L1 norm:
-        self.ranges.extend(self.map.sensors.iter()
-            .filter_map(|s| {
-                let y_diff = (s.position.y - y).abs();
-                if y_diff <= s.range {
-                    let start = s.position.x - s.range + y_diff;
-                    let end = s.position.x + s.range - y_diff;
-                    Some(CoordRange::new(start, end))
-                } else {
-                    None
-                }
-            }));
+        self.ranges.extend(self.map.sensors.iter().filter_map(|s| s.row_range(y)));
 
         self.ranges.sort_unstable_by_key(|r| r.start);
 
@@ -156,55 +329,111 @@ impl<'a> BeaconFinder<'a> {
 
     fn count_nobeacon_cells(&mut self, y: Coord) -> usize {
         self.collect_ranges(y);
-        self.joint_ranges.iter()
-            .map(|range| range.len())
-            .sum::<Coord>()
-            .try_into()
-            .unwrap()
+        let covered = self.joint_ranges.iter()
+            .map(|range| range.len() + 1)
+            .sum::<Coord>();
+
+        // every beacon is within range of its sensor, so it is always part of the covered cells
+        let beacons = self.map.beacons_in_row(y).len() as Coord;
+        (covered - beacons).try_into().unwrap()
+    }
+
+    /// Finds the first x coordinate within the given range in row y not covered by any sensor.
+    fn find_hole(&mut self, y: Coord, x_range: &RangeInclusive<Coord>) -> Option<Coord> {
+        self.collect_ranges(y);
+
+        // walk along the sorted ranges, skipping over everything that is covered
+        let mut x = *x_range.start();
+        for range in &self.joint_ranges {
+            if range.end < x {
+                continue;
+            } else if range.start > x {
+                break;
+            }
+            x = range.end + 1;
+        }
+
+        (x <= *x_range.end()).then_some(x)
     }
 
     fn find_beacon(&mut self, max: Coord) -> Point {
+        self.find_beacon_in(0..=max, 0..=max)
+    }
+
+    /// Looks for the single position within the given box not covered by any sensor.
+    fn find_beacon_in(&mut self, x_range: RangeInclusive<Coord>, y_range: RangeInclusive<Coord>)
+        -> Point
+    {
         // do the same as in part 1, but this time, look for a hole in the range of coordinates.
         //  corner cutting: this will not check whether the hole is unique.
         //  searching only the borders of sensors is probably more efficient than this, but meh.
-        for y in 0..max {
-            self.collect_ranges(y);
-
-            // edge case: only one range with the hole right at the x border
-            if self.joint_ranges.len() == 1 {
-                let range = self.joint_ranges[0];
-                if range.start == 1 {
-                    return Point::new(0, y);
-                } else if range.end == (max - 1) {
-                    return Point::new(max, y);
-                }
-            }
-
-            for window in self.joint_ranges.windows(2) {
-                if window[0].end >= 0 && window[1].start <= max {
-                    return Point::new(window[0].end + 1, y);
-                }
+        for y in y_range {
+            if let Some(x) = self.find_hole(y, &x_range) {
+                return Point::new(x, y);
             }
         }
 
         panic!("No hole found");
     }
+
+    /// Same as `find_beacon`, but scans the rows on multiple threads. Since the scratch buffers
+    /// can't be shared, each thread gets its own finder.
+    #[cfg(feature = "rayon")]
+    #[allow(dead_code)]
+    fn find_beacon_parallel(&self, max: Coord) -> Point {
+        use rayon::prelude::*;
+
+        let x_range = 0..=max;
+        (0..=max).into_par_iter()
+            .map_init(|| BeaconFinder::new(self.map), |finder, y| {
+                finder.find_hole(y, &x_range).map(|x| Point::new(x, y))
+            })
+            .find_map_any(|hole| hole)
+            .expect("No hole found")
+    }
 }
 
 
 static INPUT: &str = include_str!("inputs/day15.txt");
 
-pub fn run() {
-    let map = Map::parse(INPUT);
-    let mut finder = BeaconFinder::new(&map);
-    let row = 2000000;
-    let part1 = finder.count_nobeacon_cells(row);
-    println!("Positions at which no beacon can be present in row {row}: {part1}");
-
-    let max = 4000000;
-    let beacon = finder.find_beacon(max);
-    let part2 = beacon.x * max + beacon.y;
-    println!("Beacon at {beacon:?}. Frequency: {part2}");
+/// The row to count in for part one.
+const ROW: Coord = 2000000;
+
+/// Upper bound of both coordinates of the distress beacon in part two.
+const MAX: Coord = 4000000;
+
+fn tuning_frequency(beacon: Point) -> Coord {
+    beacon.x * MAX + beacon.y
+}
+
+
+pub struct Day15;
+
+impl Puzzle for Day15 {
+    fn input(&self) -> &'static str {
+        INPUT
+    }
+
+    fn part1(&self, input: &str) -> String {
+        let map = Map::parse(input);
+        BeaconFinder::new(&map).count_nobeacon_cells(ROW).to_string()
+    }
+
+    fn part2(&self, input: &str) -> String {
+        let map = Map::parse(input);
+        tuning_frequency(BeaconFinder::new(&map).find_beacon(MAX)).to_string()
+    }
+
+    fn run(&self) {
+        let map = Map::parse(INPUT);
+        let mut finder = BeaconFinder::new(&map);
+        let part1 = finder.count_nobeacon_cells(ROW);
+        println!("Positions at which no beacon can be present in row {ROW}: {part1}");
+
+        let beacon = finder.find_beacon(MAX);
+        let part2 = tuning_frequency(beacon);
+        println!("Beacon at {beacon:?}. Frequency: {part2}");
+    }
 }
 
 
@@ -212,8 +441,113 @@ pub fn run() {
 mod test {
     use super::*;
 
+    const EXAMPLE: &str = "Sensor at x=2, y=18: closest beacon is at x=-2, y=15
+                           Sensor at x=9, y=16: closest beacon is at x=10, y=16
+                           Sensor at x=13, y=2: closest beacon is at x=15, y=3
+                           Sensor at x=12, y=14: closest beacon is at x=10, y=16
+                           Sensor at x=10, y=20: closest beacon is at x=10, y=16
+                           Sensor at x=14, y=17: closest beacon is at x=10, y=16
+                           Sensor at x=8, y=7: closest beacon is at x=2, y=10
+                           Sensor at x=2, y=0: closest beacon is at x=2, y=10
+                           Sensor at x=0, y=11: closest beacon is at x=2, y=10
+                           Sensor at x=20, y=14: closest beacon is at x=25, y=17
+                           Sensor at x=17, y=20: closest beacon is at x=21, y=22
+                           Sensor at x=16, y=7: closest beacon is at x=15, y=3
+                           Sensor at x=14, y=3: closest beacon is at x=15, y=3
+                           Sensor at x=20, y=1: closest beacon is at x=15, y=3";
+
     #[test]
     fn example() {
+        let input = EXAMPLE;
+        let map = Map::parse(input);
+        assert_eq!(map.sensors.len(), 14);
+        assert_eq!(map.sensors[3].position, Point::new(12, 14));
+
+        assert_eq!(map.beacons_in_row(10), &[2]);
+        assert_eq!(map.beacons_in_row(16), &[10]);
+        assert!(map.beacons_in_row(11).is_empty());
+
+        let mut finder = BeaconFinder::new(&map);
+        assert_eq!(finder.count_nobeacon_cells(10), 26);
+        let contributions = map.row_contributions(10);
+        let indices = contributions.iter().map(|(i, _)| *i).collect::<Vec<_>>();
+        assert_eq!(indices, &[3, 6, 7, 8, 9, 11]);
+        let (_, range) = contributions[1];
+        assert_eq!((range.start, range.end), (2, 14));
+        let row_16 = map.total_covered_area((-100..=100, 16..=16));
+        assert_eq!(finder.count_nobeacon_cells(16), row_16 - 1);
+        assert_eq!(finder.find_beacon(20), Point::new(14, 11));
+        assert_eq!(map.find_beacon_diagonal(20), Point::new(14, 11));
+
+        assert!(!map.is_covered(Point::new(14, 11)));
+        assert!(map.is_covered(Point::new(13, 11)));
+        assert!(map.is_covered(Point::new(8, 7)));
+        assert!(!map.is_covered(Point::new(-100, -100)));
+
+        // everything but the distress beacon is covered
+        assert_eq!(map.total_covered_area((0..=20, 0..=20)), 21 * 21 - 1);
+        assert_eq!(map.total_covered_area((14..=14, 11..=11)), 0);
+        let hole = Point::new(14, 11);
+        assert_eq!(map.largest_uncovered_rect(20), Some((hole, hole)));
+
+        // same scenario, shifted away from the origin
+        let offset = Point::new(-100, 50);
+        let shifted_map = Map {
+            sensors: map.sensors.iter()
+                .map(|s| Sensor {
+                    position: s.position + offset,
+                    beacon: s.beacon + offset,
+                    range: s.range,
+                })
+                .collect(),
+        };
+        let mut finder = BeaconFinder::new(&shifted_map);
+        let beacon = finder.find_beacon_in(-100..=-80, 50..=70);
+        assert_eq!(beacon, Point::new(-86, 61));
+    }
+
+    #[test]
+    fn reader() {
+        let map = Map::from_reader(std::io::Cursor::new(EXAMPLE)).unwrap();
+        assert_eq!(map.sensors.len(), 14);
+        assert_eq!(map.sensors.len(), Map::parse(EXAMPLE).sensors.len());
+        assert_eq!(map.sensors[3].position, Point::new(12, 14));
+
+        let broken = "Sensor at x=2, y=18: closest beacon is at x=-2, y=15\n\nSensor at x=9\n";
+        assert_eq!(Map::from_reader(std::io::Cursor::new(broken)).err(),
+            Some(SensorParseError::MissingPart));
+        let broken = "Sensor at x=2, y=18: closest beacon is at x=-2, z=15";
+        assert_eq!(Map::from_reader(std::io::Cursor::new(broken)).err(),
+            Some(SensorParseError::UnknownDimension("z".to_string())));
+        let broken = "Sensor at x=2, y=1.5: closest beacon is at x=-2, y=15";
+        assert_eq!(Map::from_reader(std::io::Cursor::new(broken)).err(),
+            Some(SensorParseError::BadCoordinate));
+    }
+
+    #[test]
+    fn diagonal() {
+        // the only hole is in the corner, with no sensor boundary lines around it
+        let map = Map::parse("Sensor at x=3, y=3: closest beacon is at x=3, y=8");
+        assert_eq!(map.find_beacon_diagonal(4), Point::new(0, 0));
+
+        let map = Map::parse(INPUT);
+        assert_eq!(map.find_beacon_diagonal(MAX), BeaconFinder::new(&map).find_beacon(MAX));
+    }
+
+    #[test]
+    fn uncovered_rect() {
+        // covers (0, 0), (1, 0) and (0, 1), leaving the square from (1, 1) on free
+        let map = Map::parse("Sensor at x=0, y=0: closest beacon is at x=1, y=0");
+        assert_eq!(map.largest_uncovered_rect(4), Some((Point::new(1, 1), Point::new(4, 4))));
+        assert_eq!(map.largest_uncovered_rect(0), None);
+
+        let map = Map::parse("Sensor at x=2, y=2: closest beacon is at x=12, y=2");
+        assert_eq!(map.largest_uncovered_rect(4), None);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel() {
         let input = "Sensor at x=2, y=18: closest beacon is at x=-2, y=15
                      Sensor at x=9, y=16: closest beacon is at x=10, y=16
                      Sensor at x=13, y=2: closest beacon is at x=15, y=3
@@ -229,11 +563,7 @@ mod test {
                      Sensor at x=14, y=3: closest beacon is at x=15, y=3
                      Sensor at x=20, y=1: closest beacon is at x=15, y=3";
         let map = Map::parse(input);
-        assert_eq!(map.sensors.len(), 14);
-        assert_eq!(map.sensors[3].position, Point::new(12, 14));
-
-        let mut finder = BeaconFinder::new(&map);
-        assert_eq!(finder.count_nobeacon_cells(10), 26);
-        assert_eq!(finder.find_beacon(20), Point::new(14, 11));
+        let finder = BeaconFinder::new(&map);
+        assert_eq!(finder.find_beacon_parallel(20), Point::new(14, 11));
     }
 }
@@ -1,4 +1,6 @@
 
+use crate::common::Puzzle;
+
 use std::fmt::Debug;
 use std::str::FromStr;
 
@@ -16,6 +18,8 @@ enum Shape {
 }
 
 impl Shape {
+    const ALL: [Self; 3] = [Self::Rock, Self::Paper, Self::Scissors];
+
     fn weak_against(&self) -> Self {
         match self {
             Self::Rock => Self::Paper, // why though?
@@ -63,6 +67,8 @@ enum PlayResult {
 }
 
 impl PlayResult {
+    const ALL: [Self; 3] = [Self::Win, Self::Loss, Self::Draw];
+
     fn score(&self) -> u32 {
         match self {
             Self::Win => 6,
@@ -81,20 +87,75 @@ impl PlayResult {
 }
 
 
-fn parse_input<L, R>(input: &str) -> Vec<(L, R)>
+/// A type that can appear in the strategy guide. Lists the tokens it may be written as in either
+/// column, which are a subset of what it parses from.
+trait ColumnToken: FromStr + Debug {
+    const LEFT_TOKENS: &'static [&'static str];
+    const RIGHT_TOKENS: &'static [&'static str];
+}
+
+impl ColumnToken for Shape {
+    const LEFT_TOKENS: &'static [&'static str] = &["A", "B", "C"];
+    const RIGHT_TOKENS: &'static [&'static str] = &["X", "Y", "Z"];
+}
+
+impl ColumnToken for PlayResult {
+    // only ever our desired result, never the opponent's
+    const LEFT_TOKENS: &'static [&'static str] = &[];
+    const RIGHT_TOKENS: &'static [&'static str] = &["X", "Y", "Z"];
+}
+
+
+/// The result of playing `us` against `them`, from our point of view.
+fn outcome_of(us: Shape, them: Shape) -> PlayResult {
+    us.play(them)
+}
+
+/// Checks that `solve_play` actually picks a shape achieving the desired result, for every
+/// combination of opponent shape and result.
+#[allow(dead_code)]
+fn verify_solve_play_roundtrip() -> bool {
+    Shape::ALL.iter().all(|&them| {
+        PlayResult::ALL.iter().all(|&result| outcome_of(result.solve_play(them), them) == result)
+    })
+}
+
+
+#[derive(Debug, PartialEq, Eq)]
+enum Day2Error {
+    /// Line is not made up of two tokens.
+    Malformed,
+    /// A token is not one of those allowed for its column.
+    WrongColumnAlphabet,
+}
+
+fn try_parse_input<L, R>(input: &str) -> Result<Vec<(L, R)>, Day2Error>
 where
-    L: FromStr + Debug,
-    R: FromStr + Debug,
+    L: ColumnToken,
+    R: ColumnToken,
 {
     input.split('\n')
         .filter(|line| !line.is_empty())
         .map(|line| {
-            let (l, r) = line.trim().split_once(' ').unwrap();
-            (L::from_str(l).ok().unwrap(), R::from_str(r).ok().unwrap())
+            let (l, r) = line.trim().split_once(' ').ok_or(Day2Error::Malformed)?;
+            if !L::LEFT_TOKENS.contains(&l) || !R::RIGHT_TOKENS.contains(&r) {
+                return Err(Day2Error::WrongColumnAlphabet);
+            }
+            let l = L::from_str(l).map_err(|_| Day2Error::Malformed)?;
+            let r = R::from_str(r).map_err(|_| Day2Error::Malformed)?;
+            Ok((l, r))
         })
         .collect()
 }
 
+fn parse_input<L, R>(input: &str) -> Vec<(L, R)>
+where
+    L: ColumnToken,
+    R: ColumnToken,
+{
+    try_parse_input(input).unwrap()
+}
+
 fn calculate_score_part1(input: &str) -> u32 {
     let parsed: Vec<(Shape, Shape)> = parse_input(input);
     parsed.iter()
@@ -109,14 +170,39 @@ fn calculate_score_part2(input: &str) -> u32 {
         .sum()
 }
 
+/// The best possible total score against the given opponent shapes, achieved by always winning.
+#[allow(dead_code)]
+fn max_achievable_score(opponent: &[Shape]) -> u32 {
+    opponent.iter()
+        .map(|them| them.weak_against().score() + PlayResult::Win.score())
+        .sum()
+}
+
 static INPUT: &str = include_str!("inputs/day2.txt");
 
-pub fn run() {
-    let part1 = calculate_score_part1(INPUT);
-    println!("Score if second column is a shape: {part1}");
 
-    let part2 = calculate_score_part2(INPUT);
-    println!("Score if second column is a play result: {part2}");
+pub struct Day2;
+
+impl Puzzle for Day2 {
+    fn input(&self) -> &'static str {
+        INPUT
+    }
+
+    fn part1(&self, input: &str) -> String {
+        calculate_score_part1(input).to_string()
+    }
+
+    fn part2(&self, input: &str) -> String {
+        calculate_score_part2(input).to_string()
+    }
+
+    fn run(&self) {
+        let part1 = calculate_score_part1(INPUT);
+        println!("Score if second column is a shape: {part1}");
+
+        let part2 = calculate_score_part2(INPUT);
+        println!("Score if second column is a play result: {part2}");
+    }
 }
 
 
@@ -135,5 +221,33 @@ mod test {
 
         let score = calculate_score_part2(input);
         assert_eq!(score, 12);
+
+        let parsed = try_parse_input::<Shape, Shape>("X Y");
+        assert_eq!(parsed, Err(Day2Error::WrongColumnAlphabet));
+        let parsed = try_parse_input::<Shape, PlayResult>("A B");
+        assert_eq!(parsed, Err(Day2Error::WrongColumnAlphabet));
+        let parsed = try_parse_input::<Shape, PlayResult>("AX");
+        assert_eq!(parsed, Err(Day2Error::Malformed));
+        let parsed = try_parse_input::<PlayResult, Shape>("Z X");
+        assert_eq!(parsed, Err(Day2Error::WrongColumnAlphabet));
+
+        let opponent = [Shape::Rock, Shape::Paper, Shape::Scissors];
+        assert_eq!(max_achievable_score(&opponent), 8 + 9 + 7);
+    }
+
+    #[test]
+    fn solve_play_roundtrip() {
+        assert!(verify_solve_play_roundtrip());
+
+        for them in Shape::ALL {
+            for result in PlayResult::ALL {
+                assert_eq!(outcome_of(result.solve_play(them), them), result,
+                    "{result:?} against {them:?}");
+            }
+        }
+
+        assert_eq!(outcome_of(Shape::Paper, Shape::Rock), PlayResult::Win);
+        assert_eq!(outcome_of(Shape::Paper, Shape::Scissors), PlayResult::Loss);
+        assert_eq!(outcome_of(Shape::Paper, Shape::Paper), PlayResult::Draw);
     }
 }
@@ -1,4 +1,6 @@
 
+use crate::common::Puzzle;
+
 use std::str::FromStr;
 
 
@@ -41,6 +43,11 @@ impl Rucksack {
         &mut self.0
     }
 
+    fn compartments(&self) -> (&[Item], &[Item]) {
+        let mid = self.0.len() / 2;
+        self.0.split_at(mid)
+    }
+
     fn compartments_mut(&mut self) -> (&mut [Item], &mut [Item]) {
         let mid = self.0.len() / 2;
         self.0.split_at_mut(mid)
@@ -63,6 +70,20 @@ impl Rucksack {
 
         common_item.expect("No common item")
     }
+
+    /// Finds every pair of indices at which the left and right compartment hold the same item.
+    /// Indices are relative to the start of each compartment.
+    #[allow(dead_code)]
+    fn common_item_positions(&self) -> Vec<(usize, usize)> {
+        let (left, right) = self.compartments();
+        left.iter()
+            .enumerate()
+            .flat_map(|(left_index, left_item)| right.iter()
+                .enumerate()
+                .filter(move |(_, right_item)| *right_item == left_item)
+                .map(move |(right_index, _)| (left_index, right_index)))
+            .collect()
+    }
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -71,28 +92,51 @@ enum RucksackParseError {
     OddItemCount,
 }
 
-impl FromStr for Rucksack {
-    type Err = RucksackParseError;
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
+impl Rucksack {
+    /// Parses a rucksack without checking whether it can be split into two compartments.
+    fn from_str_unchecked(s: &str) -> Result<Self, RucksackParseError> {
         let mut items = Vec::new();
         for c in s.chars() {
-            let item = Item::try_from(c).map_err(|e| RucksackParseError::InvalidItemChar(e))?;
+            let item = Item::try_from(c).map_err(RucksackParseError::InvalidItemChar)?;
             items.push(item);
         }
+        Ok(Self(items))
+    }
+}
 
-        if items.len() % 2 != 0 {
+impl FromStr for Rucksack {
+    type Err = RucksackParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rucksack = Self::from_str_unchecked(s)?;
+        if rucksack.0.len() % 2 != 0 {
             Err(RucksackParseError::OddItemCount)
         } else {
-            Ok(Self(items))
+            Ok(rucksack)
         }
     }
 }
 
 
+/// Parses one rucksack per line, ignoring surrounding whitespace and blank lines. Errors are tagged
+/// with the 1-based line they occurred on.
+fn try_parse_input(input: &str) -> Result<Vec<Rucksack>, (usize, RucksackParseError)> {
+    input.lines()
+        .enumerate()
+        .map(|(index, line)| (index, line.trim()))
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(index, line)| Rucksack::from_str(line).map_err(|e| (index + 1, e)))
+        .collect()
+}
+
 fn parse_input(input: &str) -> Vec<Rucksack> {
+    try_parse_input(input).unwrap()
+}
+
+/// Same as `parse_input`, but accepts rucksacks that can't be split into compartments.
+fn parse_input_unchecked(input: &str) -> Vec<Rucksack> {
     input.split('\n')
         .filter(|line| !line.is_empty())
-        .map(|line| Rucksack::from_str(line.trim()).expect("Malformed rucksack"))
+        .map(|line| Rucksack::from_str_unchecked(line.trim()).expect("Malformed rucksack"))
         .collect()
 }
 
@@ -106,39 +150,100 @@ fn solve_part1(input: &str) -> Priority {
     total_prio
 }
 
+#[derive(Debug, PartialEq, Eq)]
+enum Day3Error {
+    /// Groups need at least one rucksack.
+    EmptyGroup,
+    NoBadge { group: usize },
+    AmbiguousBadge { group: usize },
+}
+
+/// Finds the single item type carried by all rucksacks of the group. Reorders the items of all but
+/// the first rucksack.
+fn find_badge(group: &mut [Rucksack], group_index: usize) -> Result<Item, Day3Error> {
+    let (first, others) = group.split_first_mut().unwrap();
+    for rucksack in others.iter_mut() {
+        rucksack.all_mut().sort_unstable();
+    }
+    let mut badge = None;
+    for item in first.all() {
+        if others.iter().all(|rucksack| rucksack.all().binary_search(item).is_ok()) {
+            // found badge
+            if badge.map(|i| i != *item).unwrap_or(false)  {
+                return Err(Day3Error::AmbiguousBadge { group: group_index });
+            }
+            badge = Some(*item);
+        }
+    }
+    badge.ok_or(Day3Error::NoBadge { group: group_index })
+}
+
+/// Sums up the badge priorities of all groups of `group_size` rucksacks.
+fn try_solve_part2(input: &str, group_size: usize) -> Result<Priority, Day3Error> {
+    if group_size == 0 {
+        return Err(Day3Error::EmptyGroup);
+    }
+    let mut rucksacks = parse_input_unchecked(input);
+    let mut total_badge_prio = 0;
+    for (group_index, group) in rucksacks.chunks_mut(group_size).enumerate() {
+        total_badge_prio += find_badge(group, group_index)?.priority();
+    }
+    Ok(total_badge_prio)
+}
+
 fn solve_part2(input: &str) -> Priority {
+    match try_solve_part2(input, 3) {
+        Ok(prio) => prio,
+        Err(Day3Error::EmptyGroup) => unreachable!(),
+        Err(Day3Error::NoBadge { group }) => panic!("No badge found in group {group}"),
+        Err(Day3Error::AmbiguousBadge { group }) =>
+            panic!("More than one common item type in group {group}"),
+    }
+}
+
+/// Solves both parts while only parsing the input once. Every rucksack is visited once per part,
+/// group by group.
+fn solve_both(input: &str) -> (Priority, Priority) {
     let mut rucksacks = parse_input(input);
+    let mut total_prio = 0;
     let mut total_badge_prio = 0;
-    for group in rucksacks.chunks_mut(3) {
-        group[1].all_mut().sort_unstable();
-        group[2].all_mut().sort_unstable();
-        let mut badge = None;
-        for item in group[0].all() {
-            if group[1].all().binary_search(item).is_ok() {
-                // candidate for badge
-                if group[2].all().binary_search(item).is_ok() {
-                    // found badge
-                    if badge.map(|i| i != *item).unwrap_or(false)  {
-                        panic!("More than one common item type: {item:?}");
-                    }
-                    badge = Some(*item);
-                }
-            }
+    for (group_index, group) in rucksacks.chunks_mut(3).enumerate() {
+        // finding the common item only reorders items within the rucksack, so the badge stays
+        for rucksack in group.iter_mut() {
+            total_prio += rucksack.find_common_item().priority();
+        }
+        match find_badge(group, group_index) {
+            Ok(badge) => total_badge_prio += badge.priority(),
+            Err(e) => panic!("Badge error {e:?}"),
         }
-        total_badge_prio += badge.expect("No badge found").priority();
     }
-    total_badge_prio
+    (total_prio, total_badge_prio)
 }
 
-
 static INPUT: &str = include_str!("inputs/day3.txt");
 
-pub fn run() {
-    let part1 = solve_part1(INPUT);
-    println!("Total priorities of common items in compartments: {part1}");
 
-    let part2 = solve_part2(INPUT);
-    println!("Total priorities of common items in groups of three rucksacks: {part2}");
+pub struct Day3;
+
+impl Puzzle for Day3 {
+    fn input(&self) -> &'static str {
+        INPUT
+    }
+
+    fn part1(&self, input: &str) -> String {
+        solve_part1(input).to_string()
+    }
+
+    fn part2(&self, input: &str) -> String {
+        solve_part2(input).to_string()
+    }
+
+    fn run(&self) {
+        let (part1, part2) = solve_both(INPUT);
+        println!("Total priorities of common items in compartments: {part1}");
+
+        println!("Total priorities of common items in groups of three rucksacks: {part2}");
+    }
 }
 
 
@@ -158,6 +263,27 @@ mod test {
         assert_eq!(Rucksack::from_str("OwO what's this?"),
             Err(RucksackParseError::InvalidItemChar(' ')));
         assert_eq!(Rucksack::from_str("Ziebelzobel"), Err(RucksackParseError::OddItemCount));
+        assert_eq!(Rucksack::from_str_unchecked("abc").unwrap().0, &expected[..3]);
+        assert!(Rucksack::from_str_unchecked("Ziebelzobel").is_ok());
+        assert_eq!(Rucksack::from_str_unchecked("Zwiebel?"),
+            Err(RucksackParseError::InvalidItemChar('?')));
+    }
+
+    #[test]
+    fn parse_lines() {
+        let input = "vJrwpWtwJgWrhcsFMMfFFhFp  \n\n   \n  PmmdzqPrVvPwwTWBwg\n";
+        assert_eq!(try_parse_input(input).unwrap().len(), 2);
+
+        let input = "vJrwpWtwJgWrhcsFMMfFFhFp
+
+                     jqHRNqRjqzjGDLGLrsFMfFZSrLrFZsSL
+                     PmmdzqPrVvPwwTWBw
+                     wMqvLMZHhHMvwLHjbvcjnnSBnvTQFn";
+        assert_eq!(try_parse_input(input).err(), Some((4, RucksackParseError::OddItemCount)));
+
+        let input = "vJrwpWtwJgWrhcsFMMfFFhFp\nPmmdzq-rVvPwwTWBwg";
+        assert_eq!(try_parse_input(input).err(),
+            Some((2, RucksackParseError::InvalidItemChar('-'))));
     }
 
     fn check_common_item(rucksack_def: &str, expected_common_item: char) {
@@ -175,6 +301,18 @@ mod test {
         check_common_item("CrZsJsPPZsGzwwsLwLmpwMDw", 's');
     }
 
+    #[test]
+    fn common_item_position() {
+        let rucksack = Rucksack::from_str("vJrwpWtwJgWrhcsFMMfFFhFp").unwrap();
+        assert_eq!(rucksack.common_item_positions(), &[(4, 11)]);
+        let (left, right) = rucksack.compartments();
+        assert_eq!(left[4], Item('p'));
+        assert_eq!(right[11], Item('p'));
+
+        let rucksack = Rucksack::from_str("aXaYbaZc").unwrap();
+        assert_eq!(rucksack.common_item_positions(), &[(0, 1), (2, 1)]);
+    }
+
     #[test]
     fn example_p1() {
         let input = "
@@ -199,5 +337,36 @@ mod test {
             CrZsJsPPZsGzwwsLwLmpwMDw";
         let part2 = solve_part2(input);
         assert_eq!(part2, 70);
+        assert_eq!(solve_both(input), (157, 70));
+    }
+
+    #[test]
+    fn malformed_groups() {
+        let input = "
+            abcdef
+            ghiajk
+            lmnaop
+            qrstuv
+            wxyzAB
+            CDEFGH";
+        assert_eq!(try_solve_part2(input, 3), Err(Day3Error::NoBadge { group: 1 }));
+
+        let input = "
+            abcdef
+            abghij
+            abklmn";
+        assert_eq!(try_solve_part2(input, 3), Err(Day3Error::AmbiguousBadge { group: 0 }));
+
+        let input = "
+            abcdef
+            ghiajk";
+        assert_eq!(try_solve_part2(input, 2), Ok(1));
+        assert_eq!(try_solve_part2(input, 0), Err(Day3Error::EmptyGroup));
+
+        let input = "
+            abc
+            ghiajk
+            ax";
+        assert_eq!(try_solve_part2(input, 3), Ok(1));
     }
 }
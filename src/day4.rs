@@ -1,4 +1,6 @@
 
+use crate::common::Puzzle;
+
 use std::array;
 use std::str::FromStr;
 
@@ -19,6 +21,11 @@ impl IdRange {
         }
     }
 
+    /// Number of sections in this range.
+    fn len(&self) -> usize {
+        self.end - self.start
+    }
+
     fn contains(&self, point: usize) -> bool {
         point >= self.start && point < self.end
     }
@@ -69,15 +76,101 @@ where
         .count()
 }
 
+/// Counts the pairs that don't overlap at all.
+#[allow(dead_code)]
+fn count_disjoint(input: &str) -> usize {
+    count_ranges(input, |a, b| !a.overlaps_range(b) && !b.overlaps_range(a))
+}
+
+/// Sorts the given ranges and merges all that overlap or are adjacent, leaving only disjoint ranges.
+fn merge_ranges(mut ranges: Vec<IdRange>) -> Vec<IdRange> {
+    ranges.sort_unstable_by_key(|r| r.start);
+    let mut merged: Vec<IdRange> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+/// Counts the distinct section IDs covered by any elf.
+#[allow(dead_code)]
+fn total_covered_sections(input: &str) -> usize {
+    let ranges = parse_input(input).into_iter().flatten().collect();
+    merge_ranges(ranges).iter()
+        .map(IdRange::len)
+        .sum()
+}
+
+/// Number of sections each elf is assigned, in input order.
+#[allow(dead_code)]
+fn sections_per_elf(input: &str) -> Vec<usize> {
+    parse_input(input).iter()
+        .flatten()
+        .map(IdRange::len)
+        .collect()
+}
+
+/// Checks every range against every other one. Entry `[i][j]` is true if range i and j overlap.
+#[allow(dead_code)]
+fn overlap_matrix(ranges: &[IdRange]) -> Vec<Vec<bool>> {
+    ranges.iter()
+        .map(|a| ranges.iter()
+            .map(|b| a.overlaps_range(b) || b.overlaps_range(a))
+            .collect())
+        .collect()
+}
+
+/// Finds all pairs `(i, j)` with `i < j` of overlapping ranges by sweeping over the range starts.
+/// The pairs are sorted.
+#[allow(dead_code)]
+fn overlapping_pairs(ranges: &[IdRange]) -> Vec<(usize, usize)> {
+    let mut order = (0..ranges.len()).collect::<Vec<_>>();
+    order.sort_unstable_by_key(|i| ranges[*i].start);
+
+    // every range still in here is either paired up with the current one or removed for good, so
+    // this stays in O(n log n + k)
+    let mut active: Vec<usize> = Vec::new();
+    let mut pairs = Vec::new();
+    for i in order {
+        let start = ranges[i].start;
+        active.retain(|a| ranges[*a].end > start);
+        pairs.extend(active.iter().map(|a| (*a.min(&i), *a.max(&i))));
+        active.push(i);
+    }
+
+    pairs.sort_unstable();
+    pairs
+}
+
 
 static INPUT: &str = include_str!("inputs/day4.txt");
 
-pub fn run() {
-    let enclosed = count_ranges(INPUT, IdRange::contains_range);
-    println!("Completely enclosed ranges: {enclosed}");
 
-    let overlapping = count_ranges(INPUT, IdRange::overlaps_range);
-    println!("Overlapping ranges: {overlapping}");
+pub struct Day4;
+
+impl Puzzle for Day4 {
+    fn input(&self) -> &'static str {
+        INPUT
+    }
+
+    fn part1(&self, input: &str) -> String {
+        count_ranges(input, IdRange::contains_range).to_string()
+    }
+
+    fn part2(&self, input: &str) -> String {
+        count_ranges(input, IdRange::overlaps_range).to_string()
+    }
+
+    fn run(&self) {
+        let enclosed = count_ranges(INPUT, IdRange::contains_range);
+        println!("Completely enclosed ranges: {enclosed}");
+
+        let overlapping = count_ranges(INPUT, IdRange::overlaps_range);
+        println!("Overlapping ranges: {overlapping}");
+    }
 }
 
 
@@ -103,5 +196,55 @@ mod test {
 
         let overlapping = count_ranges(input, IdRange::overlaps_range);
         assert_eq!(overlapping, 4);
+        assert_eq!(count_disjoint(input), 2);
+        assert_eq!(count_disjoint(input), parsed.len() - overlapping);
+
+        assert_eq!(total_covered_sections(input), 8);
+
+        let sections = sections_per_elf(input);
+        assert_eq!(sections.len(), 12);
+        assert_eq!(sections[..2], [3, 3]);
+        assert_eq!(sections, &[3, 3, 2, 2, 3, 3, 7, 5, 1, 3, 5, 5]);
+        assert_eq!(IdRange::new(6, 6).len(), 1);
+    }
+
+    #[test]
+    fn merge() {
+        let ranges = vec![IdRange::new(7, 9), IdRange::new(1, 2), IdRange::new(3, 4),
+            IdRange::new(12, 12), IdRange::new(8, 10)];
+        let merged = merge_ranges(ranges);
+        assert_eq!(merged, &[IdRange::new(1, 4), IdRange::new(7, 10), IdRange::new(12, 12)]);
+    }
+
+    #[test]
+    fn pairwise_overlaps() {
+        let input = "
+            2-4,6-8
+            2-3,4-5
+            5-7,7-9
+            2-8,3-7
+            6-6,4-6
+            2-6,4-8";
+        let ranges = parse_input(input).into_iter().flatten().collect::<Vec<_>>();
+        let matrix = overlap_matrix(&ranges);
+        assert!(matrix[0][2]);
+        assert!(!matrix[0][1]);
+        assert!(!matrix[2][3]);
+        assert!(matrix[4][5]);
+        assert!(matrix[8][9]);
+        assert!((0..ranges.len()).all(|i| matrix[i][i]));
+
+        let pairs = overlapping_pairs(&ranges);
+        assert!(pairs.contains(&(0, 2)));
+        assert!(pairs.contains(&(4, 5)));
+        assert!(pairs.contains(&(6, 11)));
+        assert!(!pairs.contains(&(0, 1)));
+        assert!(!pairs.contains(&(2, 3)));
+
+        let expected = (0..ranges.len())
+            .flat_map(|i| ((i + 1)..ranges.len()).map(move |j| (i, j)))
+            .filter(|(i, j)| matrix[*i][*j])
+            .collect::<Vec<_>>();
+        assert_eq!(pairs, expected);
     }
 }
@@ -1,7 +1,7 @@
 
 //! This one's probably a bit overdone, but the most correct solution I could come up with.
 
-use crate::common::{Words, WordsError, GetMuts};
+use crate::common::{Words, WordsError, GetMuts, Puzzle};
 
 use std::str::FromStr;
 
@@ -55,40 +55,57 @@ impl FromStr for Instruction {
 }
 
 
-struct CrateRowIterator<'a>(std::str::Chars<'a>);
+struct CrateRowIterator<'a> {
+    chars: std::str::Chars<'a>,
+    /// Characters per crate, including the brackets but not the separator.
+    width: usize,
+}
 
 impl<'a> CrateRowIterator<'a> {
+    const DEFAULT_WIDTH: usize = 3;
+
     fn new(s: &'a str) -> Self {
-        Self(s.chars())
+        Self::with_width(s, Self::DEFAULT_WIDTH)
+    }
+
+    /// Iterates over a row of crates that are `width` characters wide each, brackets included.
+    fn with_width(s: &'a str, width: usize) -> Self {
+        assert!(width >= 2, "Crates need room for their brackets");
+        Self {
+            chars: s.chars(),
+            width,
+        }
     }
 }
 
-impl Iterator for CrateRowIterator<'_> {
-    type Item = Crate;
+impl<'a> Iterator for CrateRowIterator<'a> {
+    type Item = Crate<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        let rest = self.chars.as_str();
+
         // first character may be None. in that case, iterator is at end.
-        let first = self.0.next()?;
+        self.chars.next()?;
 
-        let mut crate_spec = [first, '\0', '\0'];
-        for c in &mut crate_spec[1..] {
-            *c = if let Some(c) = self.0.next() {
-                c
-            } else {
+        for _ in 1..self.width {
+            if self.chars.next().is_none() {
                 return Some(Crate::Error(CrateError::MissingCharacter));
-            };
+            }
         }
+        let crate_spec = &rest[..(rest.len() - self.chars.as_str().len())];
 
         // check and consume crate separator
-        match self.0.next() {
+        match self.chars.next() {
             Some(' ') | None => (),
             Some(_) => return Some(Crate::Error(CrateError::BadTrailingCharacter)),
         }
 
-        match crate_spec {
-            [' ', ' ', ' '] => Some(Crate::Missing),
-            ['[', sym, ']'] => Some(Crate::Labeled(sym)),
-            _ => Some(Crate::Error(CrateError::BadCrateSpec)),
+        if crate_spec.chars().all(|c| c == ' ') {
+            Some(Crate::Missing)
+        } else if let Some(label) = crate_spec.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+            Some(Crate::Labeled(label))
+        } else {
+            Some(Crate::Error(CrateError::BadCrateSpec))
         }
     }
 }
@@ -102,26 +119,69 @@ enum CrateError {
 }
 
 #[derive(Debug, PartialEq, Eq)]
-enum Crate {
+enum Crate<'a> {
     Missing,
-    Labeled(char),
+    Labeled(&'a str),
     Error(CrateError),
 }
 
 
+#[derive(Debug, PartialEq, Eq)]
+enum NumberRowError {
+    /// A label is not a positive number.
+    BadNumber,
+    /// Two columns hold the stack with the given index.
+    #[allow(dead_code)]
+    DuplicateStack(usize),
+}
+
+/// Maps each column of the number row to the index of the stack it labels. Columns with a blank
+/// label are assumed to hold the stack their position suggests.
+fn parse_number_row(number_row: &str) -> Result<Vec<usize>, NumberRowError> {
+    let chars = number_row.chars().collect::<Vec<_>>();
+    let mut column_stacks = Vec::with_capacity(chars.len() / 4 + 1);
+    for (column, cell) in chars.chunks(4).enumerate() {
+        let label = cell.iter().collect::<String>();
+        let label = label.trim();
+        let index = if label.is_empty() {
+            column
+        } else {
+            label.parse::<usize>().ok()
+                .and_then(|number| number.checked_sub(1))
+                .ok_or(NumberRowError::BadNumber)?
+        };
+        if column_stacks.contains(&index) {
+            return Err(NumberRowError::DuplicateStack(index));
+        }
+        column_stacks.push(index);
+    }
+    Ok(column_stacks)
+}
+
 fn parse_input(input: &str) -> (Vec<Vec<char>>, Vec<Instruction>) {
     let (stacks_str, instructions_str) = input.split_once("\n\n").unwrap();
 
     // parse stacks, starting from the bottom
     let mut stack_lines = stacks_str.rsplit('\n');
     let number_row = stack_lines.next().unwrap();
-    let columns = number_row.trim().split("   ").count();
-    let mut stacks = vec![Vec::new(); columns];
+    let column_stacks = parse_number_row(number_row)
+        .unwrap_or_else(|e| panic!("Number row parse error {e:?}"));
+    let stack_index = |column: usize| column_stacks.get(column).copied().unwrap_or(column);
+    let stack_count = column_stacks.iter().max().map_or(0, |m| m + 1);
+    let mut stacks: Vec<Vec<char>> = vec![Vec::new(); stack_count];
     for line in stack_lines {
         for (crate_column, crate_label) in CrateRowIterator::new(line).enumerate() {
             match crate_label {
                 Crate::Missing => (),
-                Crate::Labeled(label) => stacks[crate_column].push(label),
+                Crate::Labeled(label) => {
+                    // with the default width, labels are always a single character
+                    let label = label.chars().next().unwrap();
+                    let index = stack_index(crate_column);
+                    if stacks.len() <= index {
+                        stacks.resize(index + 1, Vec::new());
+                    }
+                    stacks[index].push(label);
+                },
                 Crate::Error(e) => panic!("Crate parse error {e:?}"),
             }
         }
@@ -135,39 +195,89 @@ fn parse_input(input: &str) -> (Vec<Vec<char>>, Vec<Instruction>) {
 }
 
 
+#[derive(Debug, Copy, Clone)]
 enum CraneModel {
     CrateMover9000,
     CrateMover9001,
 }
 
-fn run_freightyard(input: &str, crane: CraneModel) -> String {
-    let (mut stacks, instructions) = parse_input(input);
+/// Executes a single instruction on the stacks.
+fn apply_instruction(stacks: &mut [Vec<char>], instruction: &Instruction, crane: CraneModel) {
+    let count = instruction.count;
 
-    for instruction in instructions {
-        let count = instruction.count;
+    let [from, to] = stacks.get_muts([instruction.from - 1, instruction.to - 1]);
 
-        let [from, to] = stacks.get_muts([instruction.from - 1, instruction.to - 1]);
+    let moved_stack = from.drain((from.len()-count)..);
 
-        let moved_stack = from.drain((from.len()-count)..);
+    match crane {
+        CraneModel::CrateMover9000 => to.extend(moved_stack.rev()),
+        CraneModel::CrateMover9001 => to.extend(moved_stack),
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum FreightError {
+    /// An instruction references a stack (1-based) that doesn't exist.
+    #[allow(dead_code)]
+    NoSuchStack(usize),
+}
 
-        match crane {
-            CraneModel::CrateMover9000 => to.extend(moved_stack.rev()),
-            CraneModel::CrateMover9001 => to.extend(moved_stack),
+/// Checks that all instructions only reference existing stacks.
+fn validate_instructions(stacks: &[Vec<char>], instructions: &[Instruction])
+    -> Result<(), FreightError>
+{
+    let valid_stacks = 1..=stacks.len();
+    for instruction in instructions {
+        for stack in [instruction.from, instruction.to] {
+            if !valid_stacks.contains(&stack) {
+                return Err(FreightError::NoSuchStack(stack));
+            }
         }
     }
+    Ok(())
+}
+
+fn try_run_freightyard(input: &str, crane: CraneModel) -> Result<String, FreightError> {
+    let (mut stacks, instructions) = parse_input(input);
+    validate_instructions(&stacks, &instructions)?;
 
-    stacks.iter().filter_map(|stack| stack.last()).cloned().collect()
+    for instruction in &instructions {
+        apply_instruction(&mut stacks, instruction, crane);
+    }
+
+    Ok(stacks.iter().filter_map(|stack| stack.last()).cloned().collect())
+}
+
+fn run_freightyard(input: &str, crane: CraneModel) -> String {
+    try_run_freightyard(input, crane).unwrap()
 }
 
 
 static INPUT: &str = include_str!("inputs/day5.txt");
 
-pub fn run() {
-    let part1 = run_freightyard(INPUT, CraneModel::CrateMover9000);
-    println!("Top crates using CrateMover 9000: {}", part1);
 
-    let part2 = run_freightyard(INPUT, CraneModel::CrateMover9001);
-    println!("Top crates using CrateMover 9001: {}", part2);
+pub struct Day5;
+
+impl Puzzle for Day5 {
+    fn input(&self) -> &'static str {
+        INPUT
+    }
+
+    fn part1(&self, input: &str) -> String {
+        run_freightyard(input, CraneModel::CrateMover9000)
+    }
+
+    fn part2(&self, input: &str) -> String {
+        run_freightyard(input, CraneModel::CrateMover9001)
+    }
+
+    fn run(&self) {
+        let part1 = run_freightyard(INPUT, CraneModel::CrateMover9000);
+        println!("Top crates using CrateMover 9000: {}", part1);
+
+        let part2 = run_freightyard(INPUT, CraneModel::CrateMover9001);
+        println!("Top crates using CrateMover 9001: {}", part2);
+    }
 }
 
 
@@ -179,10 +289,10 @@ mod test {
     fn stack_parsing() {
         let input = "[A]     [Ü] [漢]";
         let mut iter = CrateRowIterator::new(input);
-        assert_eq!(iter.next(), Some(Crate::Labeled('A')));
+        assert_eq!(iter.next(), Some(Crate::Labeled("A")));
         assert_eq!(iter.next(), Some(Crate::Missing));
-        assert_eq!(iter.next(), Some(Crate::Labeled('Ü')));
-        assert_eq!(iter.next(), Some(Crate::Labeled('漢')));
+        assert_eq!(iter.next(), Some(Crate::Labeled("Ü")));
+        assert_eq!(iter.next(), Some(Crate::Labeled("漢")));
         assert_eq!(iter.next(), None);
 
         assert_eq!(CrateRowIterator::new("[F").next(),
@@ -195,6 +305,25 @@ mod test {
             Some(Crate::Error(CrateError::BadTrailingCharacter)));
     }
 
+    #[test]
+    fn wide_crates() {
+        let input = "[AB]      [CD] [Ä漢]";
+        let crates = CrateRowIterator::with_width(input, 4).collect::<Vec<_>>();
+        assert_eq!(crates, &[Crate::Labeled("AB"), Crate::Missing, Crate::Labeled("CD"),
+            Crate::Labeled("Ä漢")]);
+
+        assert_eq!(CrateRowIterator::with_width("[AB", 4).next(),
+            Some(Crate::Error(CrateError::MissingCharacter)));
+        assert_eq!(CrateRowIterator::with_width("[AB]]", 4).next(),
+            Some(Crate::Error(CrateError::BadTrailingCharacter)));
+        assert_eq!(CrateRowIterator::with_width("[A] ", 4).next(),
+            Some(Crate::Error(CrateError::BadCrateSpec)));
+
+        // the default width still reads single character crates
+        let crates = CrateRowIterator::new("[A] [B]").collect::<Vec<_>>();
+        assert_eq!(crates, &[Crate::Labeled("A"), Crate::Labeled("B")]);
+    }
+
     #[test]
     fn instruction_parsing() {
         let input = "move 10 from 5 to 0";
@@ -251,4 +380,88 @@ mod test {
         let part2 = run_freightyard(input, CraneModel::CrateMover9001);
         assert_eq!(part2, "MCD");
     }
+
+    #[test]
+    fn blank_stack_number() {
+        let input = concat!(
+            "    [D]    \n",
+            "[N] [C]    \n",
+            "[Z] [M] [P]\n",
+            " 1       3 \n",
+            "\n",
+            "move 1 from 2 to 1\n",
+        );
+
+        assert_eq!(parse_number_row(" 1       3 "), Ok(vec![0, 1, 2]));
+        assert_eq!(parse_number_row(" 2   1 "), Ok(vec![1, 0]));
+        assert_eq!(parse_number_row(" 0   1 "), Err(NumberRowError::BadNumber));
+        assert_eq!(parse_number_row(" x "), Err(NumberRowError::BadNumber));
+        assert_eq!(parse_number_row(" 1   1 "), Err(NumberRowError::DuplicateStack(0)));
+        assert_eq!(parse_number_row("     1 "), Err(NumberRowError::DuplicateStack(0)));
+
+        let (stacks, _) = parse_input(input);
+        assert_eq!(stacks.len(), 3);
+        assert_eq!(stacks[0], &['Z', 'N']);
+        assert_eq!(stacks[1], &['M', 'C', 'D']);
+        assert_eq!(stacks[2], &['P']);
+
+        assert_eq!(run_freightyard(input, CraneModel::CrateMover9000), "DCP");
+
+        // crates sorted into stacks by their label, not their position
+        let input = concat!(
+            "[A] [B]\n",
+            " 2   1 \n",
+            "\n",
+            "move 1 from 1 to 2\n",
+        );
+        let (stacks, _) = parse_input(input);
+        assert_eq!(stacks, &[vec!['B'], vec!['A']]);
+    }
+
+    #[test]
+    fn missing_stack() {
+        let input = concat!(
+            "    [D]    \n",
+            "[N] [C]    \n",
+            "[Z] [M] [P]\n",
+            " 1   2   3 \n",
+            "\n",
+            "move 1 from 2 to 1\n",
+            "move 1 from 9 to 1\n",
+        );
+        assert_eq!(try_run_freightyard(input, CraneModel::CrateMover9000),
+            Err(FreightError::NoSuchStack(9)));
+
+        let input = input.replace("from 9", "from 0");
+        assert_eq!(try_run_freightyard(&input, CraneModel::CrateMover9001),
+            Err(FreightError::NoSuchStack(0)));
+
+        let input = input.replace("from 0 to 1", "from 3 to 4");
+        assert_eq!(try_run_freightyard(&input, CraneModel::CrateMover9001),
+            Err(FreightError::NoSuchStack(4)));
+    }
+
+    #[test]
+    fn single_instruction() {
+        let input = concat!(
+            "    [D]    \n",
+            "[N] [C]    \n",
+            "[Z] [M] [P]\n",
+            " 1   2   3 \n",
+            "\n",
+            "move 1 from 2 to 1\n",
+            "move 3 from 1 to 3\n",
+        );
+
+        let (mut stacks, instructions) = parse_input(input);
+        apply_instruction(&mut stacks, &instructions[0], CraneModel::CrateMover9000);
+        assert_eq!(stacks[0], &['Z', 'N', 'D']);
+        assert_eq!(stacks[1], &['M', 'C']);
+        assert_eq!(stacks[2], &['P']);
+
+        apply_instruction(&mut stacks, &instructions[1], CraneModel::CrateMover9001);
+        assert!(stacks[0].is_empty());
+        assert_eq!(stacks[1], &['M', 'C']);
+        assert_eq!(stacks[2], &['P', 'Z', 'N', 'D']);
+    }
 }
@@ -1,3 +1,8 @@
+use crate::common::Puzzle;
+
+use std::collections::VecDeque;
+use std::io::{BufReader, Read};
+
 
 const MAX_MARKER_LEN: usize = 14;
 
@@ -20,6 +25,21 @@ fn find_marker_end(input: &str, marker_len: usize) -> usize {
     marker_start + marker_len
 }
 
+/// Checks whether the `marker_len` characters ending at `end_offset` form a marker, and that it is
+/// the first one in the input. Marker lengths of zero or more than `MAX_MARKER_LEN` never match.
+#[allow(dead_code)]
+fn is_marker_at(input: &str, end_offset: usize, marker_len: usize) -> bool {
+    if !(1..=MAX_MARKER_LEN).contains(&marker_len) {
+        return false;
+    }
+    if !input.is_ascii() || marker_len > end_offset || end_offset > input.len() {
+        return false;
+    }
+    let bytes = &input.as_bytes()[..end_offset];
+    let candidate = &bytes[(end_offset - marker_len)..];
+    is_marker(candidate) && !bytes[..(end_offset - 1)].windows(marker_len).any(is_marker)
+}
+
 fn find_start_of_packet(input: &str) -> usize {
     find_marker_end(input, 4)
 }
@@ -29,14 +49,129 @@ fn find_start_of_message(input: &str) -> usize {
 }
 
 
+/// Incrementally scans a stream of bytes for markers, without needing the whole input at once.
+#[allow(dead_code)]
+struct MarkerScanner {
+    len: usize,
+    window: VecDeque<u8>,
+    /// Occurrences of each byte value within the window.
+    counts: [u8; 256],
+    /// Number of distinct byte values within the window.
+    distinct: usize,
+    /// Number of bytes pushed so far.
+    offset: usize,
+}
+
+#[allow(dead_code)]
+impl MarkerScanner {
+    fn new(len: usize) -> Self {
+        // the window holds one byte more than the marker before dropping the oldest, and all of
+        // them might be the same
+        assert!(len > 0, "Empty marker");
+        assert!(len < u8::MAX as usize, "Marker too long");
+        Self {
+            len,
+            window: VecDeque::with_capacity(len + 1),
+            counts: [0; 256],
+            distinct: 0,
+            offset: 0,
+        }
+    }
+
+    /// Feeds the next byte to the scanner. Returns the offset at which the marker ends if the last
+    /// `len` bytes form a marker.
+    fn push(&mut self, byte: u8) -> Option<usize> {
+        self.offset += 1;
+
+        self.window.push_back(byte);
+        self.counts[byte as usize] += 1;
+        if self.counts[byte as usize] == 1 {
+            self.distinct += 1;
+        }
+
+        if self.window.len() > self.len {
+            let dropped = self.window.pop_front().unwrap();
+            self.counts[dropped as usize] -= 1;
+            if self.counts[dropped as usize] == 0 {
+                self.distinct -= 1;
+            }
+        }
+
+        (self.distinct == self.len).then_some(self.offset)
+    }
+}
+
+/// Largest number of distinct characters in any window of `marker_len` characters. If this equals
+/// `marker_len`, the input contains a marker.
+#[allow(dead_code)]
+fn max_distinct_window(input: &str, marker_len: usize) -> usize {
+    let mut scanner = MarkerScanner::new(marker_len);
+    let mut max_distinct = 0;
+    for byte in input.bytes() {
+        scanner.push(byte);
+        if scanner.window.len() == marker_len {
+            max_distinct = max_distinct.max(scanner.distinct);
+        }
+    }
+    max_distinct
+}
+
+/// Finds the first marker and returns its end offset along with the marker's bytes, in the order
+/// they appear in the input. Returns `None` if there is no marker.
+#[allow(dead_code)]
+fn find_marker_with_chars(input: &str, marker_len: usize) -> Option<(usize, Vec<u8>)> {
+    let mut scanner = MarkerScanner::new(marker_len);
+    input.bytes()
+        .find_map(|byte| scanner.push(byte))
+        .map(|offset| (offset, scanner.window.iter().copied().collect()))
+}
+
+/// Same as `find_marker_end`, but letters that only differ in case count as the same character.
+#[allow(dead_code)]
+fn find_marker_end_ci(input: &str, marker_len: usize) -> Option<usize> {
+    let mut scanner = MarkerScanner::new(marker_len);
+    input.bytes().find_map(|byte| scanner.push(byte.to_ascii_lowercase()))
+}
+
+/// Same as `find_marker_end`, but reads the input incrementally, so it never has to be held in
+/// memory entirely. Returns `None` if the input ends before a marker is found.
+#[allow(dead_code)]
+fn find_marker_end_reader<R: Read>(reader: R, marker_len: usize) -> std::io::Result<Option<usize>> {
+    let mut scanner = MarkerScanner::new(marker_len);
+    for byte in BufReader::new(reader).bytes() {
+        if let Some(offset) = scanner.push(byte?) {
+            return Ok(Some(offset));
+        }
+    }
+    Ok(None)
+}
+
+
 static INPUT: &str = include_str!("inputs/day6.txt");
 
-pub fn run() {
-    let start_of_packet = find_start_of_packet(INPUT);
-    println!("First start-of-packet marker ends at offset: {start_of_packet}");
 
-    let start_of_message = find_start_of_message(INPUT);
-    println!("First start-of-message marker ends at offset: {start_of_message}");
+pub struct Day6;
+
+impl Puzzle for Day6 {
+    fn input(&self) -> &'static str {
+        INPUT
+    }
+
+    fn part1(&self, input: &str) -> String {
+        find_start_of_packet(input).to_string()
+    }
+
+    fn part2(&self, input: &str) -> String {
+        find_start_of_message(input).to_string()
+    }
+
+    fn run(&self) {
+        let start_of_packet = find_start_of_packet(INPUT);
+        println!("First start-of-packet marker ends at offset: {start_of_packet}");
+
+        let start_of_message = find_start_of_message(INPUT);
+        println!("First start-of-message marker ends at offset: {start_of_message}");
+    }
 }
 
 
@@ -47,6 +182,17 @@ mod test {
     fn check(input: &str, packet: usize, message: usize) {
         assert_eq!(find_start_of_packet(input), packet);
         assert_eq!(find_start_of_message(input), message);
+
+        let mut packet_scanner = MarkerScanner::new(4);
+        let mut message_scanner = MarkerScanner::new(14);
+        let bytes = input.as_bytes();
+        assert_eq!(bytes.iter().find_map(|b| packet_scanner.push(*b)), Some(packet));
+        assert_eq!(bytes.iter().find_map(|b| message_scanner.push(*b)), Some(message));
+
+        let reader = std::io::Cursor::new(input);
+        assert_eq!(find_marker_end_reader(reader, 4).unwrap(), Some(packet));
+        let reader = std::io::Cursor::new(input);
+        assert_eq!(find_marker_end_reader(reader, 14).unwrap(), Some(message));
     }
 
     #[test]
@@ -57,4 +203,83 @@ mod test {
         check("nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg", 10, 29);
         check("zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw", 11, 26);
     }
+
+    #[test]
+    fn marker_chars() {
+        let input = "mjqjpqmgbljsphdztnvjfqwrcgsmlb";
+        let (offset, chars) = find_marker_with_chars(input, 4).unwrap();
+        assert_eq!(offset, 7);
+        assert_eq!(chars, b"jpqm");
+        assert_eq!(chars, input.as_bytes()[(offset - 4)..offset]);
+
+        let (offset, chars) = find_marker_with_chars(input, 14).unwrap();
+        assert_eq!(offset, 19);
+        assert_eq!(chars, input.as_bytes()[(offset - 14)..offset]);
+
+        assert_eq!(find_marker_with_chars("abababab", 4), None);
+    }
+
+    #[test]
+    fn case_insensitive() {
+        let input = "aAbBcdef";
+        assert_eq!(find_marker_end(input, 4), 4);
+        assert_eq!(find_marker_end_ci(input, 4), Some(7));
+        assert_eq!(find_marker_end_ci("abAB", 4), None);
+        assert_eq!(find_marker_end_ci("mjqjpqmgbljsphdztnvjfqwrcgsmlb", 4), Some(7));
+        assert_eq!(find_marker_end_ci("MJQJPQMGBLJSPHDZTNVJFQWRCGSMLB", 14), Some(19));
+    }
+
+    #[test]
+    fn reader_without_marker() {
+        let reader = std::io::Cursor::new("abababab");
+        assert_eq!(find_marker_end_reader(reader, 4).unwrap(), None);
+        assert_eq!(find_marker_end_reader(std::io::empty(), 4).unwrap(), None);
+    }
+
+    #[test]
+    fn scanner_limits() {
+        let mut scanner = MarkerScanner::new(u8::MAX as usize - 1);
+        assert!((0..1000).all(|_| scanner.push(b'a').is_none()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Marker too long")]
+    fn scanner_too_long() {
+        MarkerScanner::new(u8::MAX as usize);
+    }
+
+    #[test]
+    #[should_panic(expected = "Empty marker")]
+    fn scanner_empty() {
+        MarkerScanner::new(0);
+    }
+
+    #[test]
+    fn distinct_windows() {
+        assert_eq!(max_distinct_window("aaaaaaaa", 4), 1);
+        assert_eq!(max_distinct_window("abababab", 4), 2);
+        assert_eq!(max_distinct_window("abcabcab", 4), 3);
+        assert_eq!(max_distinct_window("mjqjpqmgbljsphdztnvjfqwrcgsmlb", 4), 4);
+        assert_eq!(max_distinct_window("abc", 4), 0);
+    }
+
+    #[test]
+    fn marker_verification() {
+        assert!(is_marker_at("mjqjpqmgbljsphdztnvjfqwrcgsmlb", 7, 4));
+        assert!(is_marker_at("mjqjpqmgbljsphdztnvjfqwrcgsmlb", 19, 14));
+        assert!(is_marker_at("bvwbjplbgvbhsrlpgdmjqwftvncz", 5, 4));
+        assert!(is_marker_at("nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg", 10, 4));
+
+        // off by one in either direction
+        assert!(!is_marker_at("mjqjpqmgbljsphdztnvjfqwrcgsmlb", 6, 4));
+        assert!(!is_marker_at("mjqjpqmgbljsphdztnvjfqwrcgsmlb", 8, 4));
+
+        // out of bounds
+        assert!(!is_marker_at("abcd", 5, 4));
+        assert!(!is_marker_at("abcd", 3, 4));
+
+        // unsupported marker lengths
+        assert!(!is_marker_at("abcd", 2, 0));
+        assert!(!is_marker_at("abcdefghijklmnop", 15, 15));
+    }
 }
@@ -1,10 +1,13 @@
 
+use crate::common::Puzzle;
+
 use petgraph::Direction;
+use petgraph::algo::is_cyclic_directed;
 use petgraph::graph::{DiGraph, NodeIndex};
-use petgraph::visit::DfsPostOrder;
+use petgraph::visit::{Dfs, DfsPostOrder};
 
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 enum File<'a> {
     Regular{ name: &'a str, size: usize },
     Directory{ name: &'a str, recursive_size: Option<usize> },
@@ -34,6 +37,8 @@ enum FsError {
     AscendedPastRoot,
     BadFileSize,
     BadCommandLine,
+    DeletedRoot,
+    NameCollision,
 }
 
 
@@ -76,9 +81,120 @@ impl<'a> FileSystem<'a> {
     }
 
     fn ascend(&mut self) -> Result<(), FsError> {
-        self.pwd = self.tree.neighbors_directed(self.pwd, Direction::Incoming)
-            .next()
-            .ok_or(FsError::AscendedPastRoot)?;
+        self.pwd = self.parent(self.pwd).ok_or(FsError::AscendedPastRoot)?;
+        Ok(())
+    }
+
+    fn parent(&self, node: NodeIndex) -> Option<NodeIndex> {
+        self.tree.neighbors_directed(node, Direction::Incoming).next()
+    }
+
+    /// Iterates over the parent, grandparent etc. of the given node, up to and including root.
+    fn ancestors(&self, node: NodeIndex) -> impl Iterator<Item = NodeIndex> + '_ {
+        std::iter::successors(self.parent(node), |&ancestor| self.parent(ancestor))
+    }
+
+    /// Builds the absolute path of the given node.
+    fn path_of(&self, node: NodeIndex) -> String {
+        if node == self.root {
+            return String::from("/");
+        }
+        let mut names = std::iter::once(node)
+            .chain(self.ancestors(node))
+            .filter(|&id| id != self.root)
+            .map(|id| self.tree[id].name())
+            .collect::<Vec<_>>();
+        names.reverse();
+        names.iter().fold(String::new(), |path, name| path + "/" + name)
+    }
+
+    /// Changes the pwd along a path of slash-separated directory names. Absolute paths start at
+    /// root.
+    fn change_dir(&mut self, path: &str) -> Result<(), FsError> {
+        if path.starts_with('/') {
+            self.go_to_root();
+        }
+        for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+            if segment == ".." {
+                self.ascend()?;
+            } else {
+                self.descend(segment)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Finds the directory at the given path, without changing the pwd.
+    fn find_dir(&mut self, path: &str) -> Result<NodeIndex, FsError> {
+        let pwd = self.pwd;
+        let result = self.change_dir(path).map(|_| self.pwd);
+        self.pwd = pwd;
+        result
+    }
+
+    /// Removes a directory and everything in it. Returns the number of bytes freed. If the pwd was
+    /// inside the deleted directory, it moves to the deleted directory's parent.
+    #[allow(dead_code)]
+    fn delete_dir(&mut self, path: &str) -> Result<usize, FsError> {
+        let dir = self.find_dir(path)?;
+        let parent = self.parent(dir).ok_or(FsError::DeletedRoot)?;
+
+        let mut subtree = Vec::new();
+        let mut dfs = Dfs::new(&self.tree, dir);
+        while let Some(node_id) = dfs.next(&self.tree) {
+            subtree.push(node_id);
+        }
+        if subtree.contains(&self.pwd) {
+            self.pwd = parent;
+        }
+
+        // removing a node moves the last node into its place. by removing in descending order, the
+        // moved node is never one we still have to remove, but root and pwd might move.
+        let freed = self.tree[dir].recursive_size().unwrap_or(0);
+        subtree.sort_unstable_by(|a, b| b.cmp(a));
+        for node_id in subtree {
+            let last = NodeIndex::new(self.tree.node_count() - 1);
+            self.tree.remove_node(node_id);
+            if self.root == last {
+                self.root = node_id;
+            }
+            if self.pwd == last {
+                self.pwd = node_id;
+            }
+        }
+
+        self.update_dir_sizes();
+        Ok(freed)
+    }
+
+    /// Grafts everything in the other file system's root under this one's root. Fails without
+    /// changing anything if both roots contain a file of the same name.
+    #[allow(dead_code)]
+    fn merge(&mut self, other: FileSystem<'a>) -> Result<(), FsError> {
+        let root_children = self.tree.neighbors_directed(self.root, Direction::Outgoing)
+            .map(|child_id| self.tree[child_id].name())
+            .collect::<Vec<_>>();
+        let collides = other.tree.neighbors_directed(other.root, Direction::Outgoing)
+            .any(|child_id| root_children.contains(&other.tree[child_id].name()));
+        if collides {
+            return Err(FsError::NameCollision);
+        }
+
+        // pre-order, so parents are always copied before their children
+        let mut new_ids = vec![None; other.tree.node_count()];
+        new_ids[other.root.index()] = Some(self.root);
+        let mut dfs = Dfs::new(&other.tree, other.root);
+        while let Some(node_id) = dfs.next(&other.tree) {
+            let Some(parent_id) = other.parent(node_id) else {
+                continue;
+            };
+            let new_id = self.tree.add_node(other.tree[node_id].clone());
+            let new_parent_id = new_ids[parent_id.index()].expect("Parent not copied yet");
+            self.tree.add_edge(new_parent_id, new_id, ());
+            new_ids[node_id.index()] = Some(new_id);
+        }
+
+        self.update_dir_sizes();
         Ok(())
     }
 
@@ -87,6 +203,20 @@ impl<'a> FileSystem<'a> {
         self.tree.add_edge(self.pwd, new_node_id, ());
     }
 
+    /// Checks whether the graph is a tree rooted at root, i.e. it is acyclic, root has no parent and
+    /// every other node has exactly one.
+    fn is_tree(&self) -> bool {
+        let parent_count_ok = self.tree.node_indices().all(|node_id| {
+            let parents = self.tree.neighbors_directed(node_id, Direction::Incoming).count();
+            if node_id == self.root {
+                parents == 0
+            } else {
+                parents == 1
+            }
+        });
+        parent_count_ok && !is_cyclic_directed(&self.tree)
+    }
+
     fn update_dir_sizes(&mut self) {
         // reset recursive size on all nodes first
         for node in self.tree.node_weights_mut() {
@@ -101,8 +231,7 @@ impl<'a> FileSystem<'a> {
             let node = &self.tree[node_id];
             let size = node.recursive_size().expect("Child nodes not already visited");
 
-            let mut parent_node_ids = self.tree.neighbors_directed(node_id, Direction::Incoming);
-            if let Some(parent_node_id) = parent_node_ids.next() {
+            if let Some(parent_node_id) = self.parent(node_id) {
                 let parent_node = &mut self.tree[parent_node_id];
                 match parent_node {
                     File::Regular{ .. } => panic!("Parent of file is a regular file"),
@@ -113,6 +242,44 @@ impl<'a> FileSystem<'a> {
         }
     }
 
+    /// Lists the full paths of all directories along with their recursive size, sorted by path.
+    fn directories(&self) -> Vec<(String, usize)> {
+        let mut dirs = self.tree.node_indices()
+            .filter_map(|node_id| match &self.tree[node_id] {
+                File::Regular{ .. } => None,
+                File::Directory{ recursive_size, .. } =>
+                    Some((self.path_of(node_id), recursive_size.unwrap_or(0))),
+            })
+            .collect::<Vec<_>>();
+        dirs.sort_unstable();
+        dirs
+    }
+
+    /// Counts the directories (including the root) and regular files in the tree.
+    #[allow(dead_code)]
+    fn counts(&self) -> (usize, usize) {
+        self.tree.node_weights()
+            .fold((0, 0), |(dirs, files), node| match node {
+                File::Directory{ .. } => (dirs + 1, files),
+                File::Regular{ .. } => (dirs, files + 1),
+            })
+    }
+
+    /// Formats the directory sizes like `du` would, optionally with human-readable sizes.
+    #[allow(dead_code)]
+    fn format_du(&self, human: bool) -> String {
+        let mut out = String::new();
+        for (path, size) in self.directories() {
+            let size_str = if human {
+                human_readable_size(size)
+            } else {
+                size.to_string()
+            };
+            out.push_str(&format!("{size_str}\t{path}\n"));
+        }
+        out
+    }
+
     /// Calculates the sum over the sizes of all directories less or equal in size to 100000.
     fn calc_part1(&self) -> usize {
         self.tree.node_weights()
@@ -139,19 +306,28 @@ impl<'a> FileSystem<'a> {
     }
 }
 
+/// Formats a size in bytes using binary units, e.g. `46.1M`.
+fn human_readable_size(size: usize) -> String {
+    const UNITS: &[&str] = &["K", "M", "G", "T"];
+    if size < 1024 {
+        return size.to_string();
+    }
+    let mut scaled = size as f64 / 1024.0;
+    let mut unit = 0;
+    while scaled >= 1024.0 && unit + 1 < UNITS.len() {
+        scaled /= 1024.0;
+        unit += 1;
+    }
+    format!("{scaled:.1}{}", UNITS[unit])
+}
+
 fn parse_input(input: &str) -> Result<FileSystem<'_>, FsError> {
     let mut fs = FileSystem::new();
     let mut ls_mode = false;
     for line in input.lines().map(|line| line.trim()) {
         if let Some(cd) = line.strip_prefix("$ cd ") {
             ls_mode = false;
-            if cd == ".." {
-                fs.ascend()?;
-            } else if cd == "/" {
-                fs.go_to_root();
-            } else {
-                fs.descend(cd)?;
-            }
+            fs.change_dir(cd)?;
         } else if line == "$ ls" {
             ls_mode = true;
         } else if ls_mode {
@@ -175,6 +351,7 @@ fn parse_input(input: &str) -> Result<FileSystem<'_>, FsError> {
         }
     }
 
+    debug_assert!(fs.is_tree(), "File system is not a tree");
     fs.update_dir_sizes();
 
     Ok(fs)
@@ -183,13 +360,30 @@ fn parse_input(input: &str) -> Result<FileSystem<'_>, FsError> {
 
 static INPUT: &str = include_str!("inputs/day7.txt");
 
-pub fn run() {
-    let fs = parse_input(INPUT).unwrap();
-    let part1 = fs.calc_part1();
-    println!("Total size of all directories smaller or equal in size to 100000: {part1}");
 
-    let part2 = fs.calc_part2();
-    println!("Smallest directory to free 30000000: {part2}");
+pub struct Day7;
+
+impl Puzzle for Day7 {
+    fn input(&self) -> &'static str {
+        INPUT
+    }
+
+    fn part1(&self, input: &str) -> String {
+        parse_input(input).unwrap().calc_part1().to_string()
+    }
+
+    fn part2(&self, input: &str) -> String {
+        parse_input(input).unwrap().calc_part2().to_string()
+    }
+
+    fn run(&self) {
+        let fs = parse_input(INPUT).unwrap();
+        let part1 = fs.calc_part1();
+        println!("Total size of all directories smaller or equal in size to 100000: {part1}");
+
+        let part2 = fs.calc_part2();
+        println!("Smallest directory to free 30000000: {part2}");
+    }
 }
 
 
@@ -227,5 +421,158 @@ mod test {
         assert_eq!(root_file.recursive_size().unwrap(), 48381165);
         assert_eq!(fs.calc_part1(), 95437);
         assert_eq!(fs.calc_part2(), 24933642);
+        assert_eq!(fs.counts(), (4, 10));
+
+        let dirs = fs.directories();
+        let paths = dirs.iter().map(|(path, _)| path.as_str()).collect::<Vec<_>>();
+        assert_eq!(paths, &["/", "/a", "/a/e", "/d"]);
+        assert_eq!(fs.format_du(false), "48381165\t/\n94853\t/a\n584\t/a/e\n24933642\t/d\n");
+        assert_eq!(fs.format_du(true), "46.1M\t/\n92.6K\t/a\n584\t/a/e\n23.8M\t/d\n");
+    }
+
+    #[test]
+    fn deletion() {
+        let input = "$ cd /
+            $ ls
+            dir a
+            14848514 b.txt
+            8504156 c.dat
+            dir d
+            $ cd a
+            $ ls
+            dir e
+            29116 f
+            2557 g
+            62596 h.lst
+            $ cd e
+            $ ls
+            584 i
+            $ cd ..
+            $ cd ..
+            $ cd d
+            $ ls
+            4060174 j
+            8033020 d.log
+            5626152 d.ext
+            7214296 k
+            $ cd /a/e";
+        let mut fs = parse_input(input).unwrap();
+        let root_size = fs.tree[fs.root].recursive_size().unwrap();
+        assert_eq!(fs.delete_dir("/d"), Ok(24933642));
+        assert_eq!(fs.tree[fs.root].recursive_size().unwrap(), root_size - 24933642);
+        assert!(fs.is_tree());
+        assert_eq!(fs.path_of(fs.pwd), "/a/e");
+        assert_eq!(fs.tree.node_count(), 14 - 5);
+
+        // deleting the pwd moves it up
+        assert_eq!(fs.delete_dir("/a/e"), Ok(584));
+        assert_eq!(fs.path_of(fs.pwd), "/a");
+        let paths = fs.directories().into_iter().map(|(path, _)| path).collect::<Vec<_>>();
+        assert_eq!(paths, &["/", "/a"]);
+        assert_eq!(fs.tree[fs.root].recursive_size().unwrap(), 14848514 + 8504156 + 94853 - 584);
+
+        assert_eq!(fs.delete_dir("/d"), Err(FsError::NotFound));
+        assert_eq!(fs.delete_dir("/b.txt"), Err(FsError::NotADirectory));
+        assert_eq!(fs.delete_dir("/"), Err(FsError::DeletedRoot));
+        assert_eq!(fs.path_of(fs.pwd), "/a");
+    }
+
+    #[test]
+    fn merging() {
+        let mut fs = parse_input("$ cd /
+                                  $ ls
+                                  dir a
+                                  100 x
+                                  $ cd a
+                                  $ ls
+                                  50 y").unwrap();
+        let other = parse_input("$ cd /
+                                 $ ls
+                                 dir b
+                                 $ cd b
+                                 $ ls
+                                 dir c
+                                 30 z
+                                 $ cd c
+                                 $ ls
+                                 7 w").unwrap();
+        let fs_size = fs.tree[fs.root].recursive_size().unwrap();
+        let other_size = other.tree[other.root].recursive_size().unwrap();
+        fs.merge(other).unwrap();
+        assert!(fs.is_tree());
+        assert_eq!(fs.tree[fs.root].recursive_size(), Some(fs_size + other_size));
+        assert_eq!(fs.directories(), &[(String::from("/"), 187), (String::from("/a"), 50),
+            (String::from("/b"), 37), (String::from("/b/c"), 7)]);
+        assert_eq!(fs.counts(), (4, 4));
+
+        let colliding = parse_input("$ cd /
+                                     $ ls
+                                     dir x
+                                     $ cd x
+                                     $ ls
+                                     1 v").unwrap();
+        assert_eq!(fs.merge(colliding), Err(FsError::NameCollision));
+        assert_eq!(fs.counts(), (4, 4));
+    }
+
+    #[test]
+    fn tree_check() {
+        let mut fs = FileSystem::new();
+        fs.create_file(File::Directory{ name: "a", recursive_size: None });
+        fs.descend("a").unwrap();
+        fs.create_file(File::Directory{ name: "b", recursive_size: None });
+        fs.create_file(File::Regular{ name: "c", size: 1 });
+        fs.descend("b").unwrap();
+        assert!(fs.is_tree());
+
+        // link b back to a
+        let a = fs.parent(fs.pwd).unwrap();
+        let mut cyclic = FileSystem { tree: fs.tree.clone(), root: fs.root, pwd: fs.pwd };
+        cyclic.tree.add_edge(fs.pwd, a, ());
+        assert!(!cyclic.is_tree());
+
+        // link b back to root
+        fs.tree.add_edge(fs.pwd, fs.root, ());
+        assert!(!fs.is_tree());
+    }
+
+    #[test]
+    fn multi_segment_cd() {
+        let input = "$ cd /
+            $ ls
+            dir a
+            $ cd a
+            $ ls
+            dir e
+            $ cd e
+            $ ls
+            584 i
+            $ cd /
+            $ cd /a/e";
+        let fs = parse_input(input).unwrap();
+        assert_eq!(fs.tree[fs.pwd].name(), "e");
+        assert_eq!(fs.path_of(fs.pwd), "/a/e");
+        assert_eq!(fs.ancestors(fs.pwd).count(), 2);
+        assert_eq!(fs.ancestors(fs.pwd).last(), Some(fs.root));
+        assert_eq!(fs.path_of(fs.root), "/");
+
+        let input = "$ cd /
+            $ ls
+            dir a
+            $ cd a
+            $ ls
+            dir e
+            $ cd e/../e
+            $ ls
+            584 i
+            $ cd ../..";
+        let fs = parse_input(input).unwrap();
+        assert_eq!(fs.pwd, fs.root);
+
+        let input = "$ cd /
+            $ ls
+            dir a
+            $ cd /a/b";
+        assert_eq!(parse_input(input).err(), Some(FsError::NotFound));
     }
 }
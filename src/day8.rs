@@ -1,4 +1,6 @@
 
+use crate::common::Puzzle;
+
 use ndarray::{s, Array2, ArrayView1, ArrayViewMut1, Axis};
 
 
@@ -65,12 +67,119 @@ fn calc_visibility_map(tree_map: &Array2<u8>) -> Array2<bool> {
     vis_map
 }
 
+/// Number of trees visible from any edge.
+fn count_visible(tree_map: &Array2<u8>) -> usize {
+    calc_visibility_map(tree_map).iter().filter(|v| **v).count()
+}
+
+/// Checks whether the tree at the given position is visible from any edge.
+fn is_visible_at(map: &Array2<u8>, x: usize, y: usize) -> bool {
+    let tree = map[[y, x]];
+    let lines_of_sight = [
+        map.slice(s![y, ..x]),
+        map.slice(s![y, (x+1)..]),
+        map.slice(s![..y, x]),
+        map.slice(s![(y+1).., x]),
+    ];
+    lines_of_sight.iter().any(|line| line.iter().all(|&other| other < tree))
+}
+
+/// Calculates the visibility map as if the tree at the given position had the new height. Raising
+/// a tree only changes the lines of sight along its row and column, so only those are recomputed.
+#[allow(dead_code)]
+fn visibility_after_raise(map: &Array2<u8>, x: usize, y: usize, new_height: u8) -> Array2<bool> {
+    let mut vis_map = calc_visibility_map(map);
+    let mut raised = map.clone();
+    raised[[y, x]] = new_height;
+    for col in 0..map.ncols() {
+        vis_map[[y, col]] = is_visible_at(&raised, col, y);
+    }
+    for row in 0..map.nrows() {
+        vis_map[[row, x]] = is_visible_at(&raised, x, row);
+    }
+    vis_map
+}
+
+/// Calculates which trees are visible when looking in from a single edge. Looks along the lanes of
+/// the given axis, starting from the far end if `reversed` is set.
+fn calc_visibility_from_edge(tree_map: &Array2<u8>, axis: Axis, reversed: bool) -> Array2<bool> {
+    let mut vis_map = Array2::from_elem(tree_map.raw_dim(), false);
+    let lanes = tree_map.axis_iter(axis).zip(vis_map.axis_iter_mut(axis));
+    for (input_lane, mut output_lane) in lanes {
+        if reversed {
+            calc_visibility(input_lane.slice(s![..;-1]), output_lane.slice_mut(s![..;-1]));
+        } else {
+            calc_visibility(input_lane, output_lane);
+        }
+    }
+    vis_map
+}
+
+/// Counts the trees visible when looking in from the top, bottom, left and right edge, in that
+/// order. A tree may be visible from more than one edge.
+#[allow(dead_code)]
+fn visible_from_edges(map: &Array2<u8>) -> [usize; 4] {
+    let directions = [(COL_AXIS, false), (COL_AXIS, true), (ROW_AXIS, false), (ROW_AXIS, true)];
+    directions.map(|(axis, reversed)| {
+        calc_visibility_from_edge(map, axis, reversed).iter().filter(|v| **v).count()
+    })
+}
+
+/// Height of the tallest tree in each row.
+fn row_maxima(map: &Array2<u8>) -> Vec<u8> {
+    map.fold_axis(COL_AXIS, 0, |max, &tree| *max.max(&tree)).to_vec()
+}
+
+/// Height of the tallest tree in each column.
+fn col_maxima(map: &Array2<u8>) -> Vec<u8> {
+    map.fold_axis(ROW_AXIS, 0, |max, &tree| *max.max(&tree)).to_vec()
+}
+
+/// Calculates visibility on a forest that wraps around at the edges. Since lines of sight wrap, a
+/// tree is visible iff it is as tall as the tallest tree in it's row or column.
+#[allow(dead_code)]
+fn calc_visibility_map_toroidal(map: &Array2<u8>) -> Array2<bool> {
+    let row_max = row_maxima(map);
+    let col_max = col_maxima(map);
+    Array2::from_shape_fn(map.raw_dim(), |(y, x)| {
+        let tree = map[[y, x]];
+        tree == row_max[y] || tree == col_max[x]
+    })
+}
+
+/// Smallest height a tree at the given position would need to be visible from outside the forest.
+/// That's one more than the tallest tree along the lowest of the four lines of sight, or 0 on the
+/// edge. The result is capped at 9, the height of the tallest possible tree, so a tree of height 9
+/// might still be hidden.
+#[allow(dead_code)]
+fn min_visible_height_at(map: &Array2<u8>, x: usize, y: usize) -> u8 {
+    let lines_of_sight = [
+        map.slice(s![y, ..x]),
+        map.slice(s![y, (x+1)..]),
+        map.slice(s![..y, x]),
+        map.slice(s![(y+1).., x]),
+    ];
+    lines_of_sight.iter()
+        .map(|line| line.iter().max().map_or(0, |max| max + 1))
+        .min()
+        .unwrap()
+        .min(9)
+}
+
 
 fn count_visible_trees(input: ArrayView1<u8>, treehouse: u8) -> usize {
+    count_visible_trees_until(input, |tree| tree >= treehouse)
+}
+
+/// Counts trees up to and including the first one for which `is_blocking` returns true.
+fn count_visible_trees_until<F>(input: ArrayView1<u8>, is_blocking: F) -> usize
+where
+    F: Fn(u8) -> bool,
+{
     let mut count = 0;
     for &tree in input.iter() {
         count += 1;
-        if tree >= treehouse {
+        if is_blocking(tree) {
             break;
         }
     }
@@ -86,22 +195,74 @@ fn calc_scenic_score_at(input: &Array2<u8>, x: usize, y: usize) -> usize {
     left * right * up * down
 }
 
+/// Variant of `calc_scenic_score_at` where trees of the same height as the treehouse don't block
+/// the view. Only strictly taller ones do.
+#[allow(dead_code)]
+fn calc_scenic_score_at_strict(input: &Array2<u8>, x: usize, y: usize) -> usize {
+    let treehouse = input[[y, x]];
+    let is_blocking = |tree| tree > treehouse;
+    let left = count_visible_trees_until(input.slice(s![y, ..x;-1]), is_blocking);
+    let right = count_visible_trees_until(input.slice(s![y, (x+1)..]), is_blocking);
+    let up = count_visible_trees_until(input.slice(s![..y;-1, x]), is_blocking);
+    let down = count_visible_trees_until(input.slice(s![(y+1).., x]), is_blocking);
+    left * right * up * down
+}
+
 fn calc_scenic_score_map(input: &Array2<u8>) -> Array2<usize> {
     Array2::from_shape_fn(input.raw_dim(), |(y, x)| calc_scenic_score_at(input, x, y))
 }
 
+/// Finds the tree with the highest scenic score. Returns its location as `(x, y)` and the score.
+fn best_scenic_tree(map: &Array2<u8>) -> ((usize, usize), usize) {
+    calc_scenic_score_map(map).indexed_iter()
+        .map(|((y, x), score)| ((x, y), *score))
+        .max_by_key(|(_, score)| *score)
+        .unwrap()
+}
+
+/// Renders the tree heights, marking visible trees with brackets and hidden ones with spaces.
+#[allow(dead_code)]
+fn render_visibility(map: &Array2<u8>, vis: &Array2<bool>) -> String {
+    let mut string = String::new();
+    for (heights, visible) in map.outer_iter().zip(vis.outer_iter()) {
+        for (height, visible) in heights.iter().zip(visible.iter()) {
+            let (open, close) = if *visible { ('[', ']') } else { (' ', ' ') };
+            string.push(open);
+            string.push(char::from(b'0' + height));
+            string.push(close);
+        }
+        string.push('\n');
+    }
+    string
+}
+
 
 static INPUT: &str = include_str!("inputs/day8.txt");
 
-pub fn run() {
-    let input = parse_input(INPUT);
-    let vis_map = calc_visibility_map(&input);
-    let visible_trees = vis_map.iter().filter(|v| **v).count();
-    println!("Trees visible from outer edge: {visible_trees}");
 
-    let score_map = calc_scenic_score_map(&input);
-    let max_score = *score_map.iter().max().unwrap();
-    println!("Maximum scenic score possible: {max_score}");
+pub struct Day8;
+
+impl Puzzle for Day8 {
+    fn input(&self) -> &'static str {
+        INPUT
+    }
+
+    fn part1(&self, input: &str) -> String {
+        count_visible(&parse_input(input)).to_string()
+    }
+
+    fn part2(&self, input: &str) -> String {
+        best_scenic_tree(&parse_input(input)).1.to_string()
+    }
+
+    fn run(&self) {
+        let input = parse_input(INPUT);
+        let visible_trees = count_visible(&input);
+        println!("Trees visible from outer edge: {visible_trees}");
+
+        let ((x, y), max_score) = best_scenic_tree(&input);
+        println!("Maximum scenic score possible: {max_score} (at x={x}, y={y})");
+    }
 }
 
 
@@ -141,5 +302,122 @@ mod test {
         assert_eq!(score_map[[3, 2]], 8);
         let max_score = *score_map.iter().max().unwrap();
         assert_eq!(max_score, 8);
+
+        assert_eq!(best_scenic_tree(&map), ((2, 3), 8));
+    }
+
+    #[test]
+    fn strict_scenic_score() {
+        let map = parse_input("33333
+                               33333
+                               33333
+                               33333
+                               33333");
+        assert_eq!(calc_scenic_score_at(&map, 2, 2), 1);
+        assert_eq!(calc_scenic_score_at_strict(&map, 2, 2), 16);
+
+        // the tree at (2, 3) can now see past the 5 above it
+        let map = parse_input("30373
+                               25512
+                               65332
+                               33549
+                               35390");
+        assert_eq!(calc_scenic_score_at(&map, 2, 3), 8);
+        assert_eq!(calc_scenic_score_at_strict(&map, 2, 3), 12);
+    }
+
+    #[test]
+    fn edge_visibility() {
+        let map = parse_input("30373
+                               25512
+                               65332
+                               33549
+                               35390");
+        assert_eq!(visible_from_edges(&map), [10, 8, 11, 11]);
+
+        let union = [(COL_AXIS, false), (COL_AXIS, true), (ROW_AXIS, false), (ROW_AXIS, true)]
+            .iter()
+            .map(|&(axis, reversed)| calc_visibility_from_edge(&map, axis, reversed))
+            .reduce(|a, b| a | b)
+            .unwrap();
+        assert_eq!(union, calc_visibility_map(&map));
+        assert_eq!(union.iter().filter(|v| **v).count(), 21);
+    }
+
+    #[test]
+    fn min_visible_height() {
+        let map = parse_input("30373
+                               25512
+                               65332
+                               33549
+                               35390");
+        // the hidden 3 in the center would have to be a 4 to be seen from the right
+        assert_eq!(min_visible_height_at(&map, 2, 2), 4);
+        // the top-left 5 only has to be taller than the 0 above it
+        assert_eq!(min_visible_height_at(&map, 1, 1), 1);
+        assert_eq!(min_visible_height_at(&map, 0, 3), 0);
+        assert_eq!(min_visible_height_at(&map, 4, 4), 0);
+
+        let vis_map = calc_visibility_map(&map);
+        for ((y, x), &height) in map.indexed_iter() {
+            assert_eq!(vis_map[[y, x]], height >= min_visible_height_at(&map, x, y));
+        }
+    }
+
+    #[test]
+    fn raised_tree() {
+        let map = parse_input("30373
+                               25512
+                               65332
+                               33549
+                               35390");
+        let before = calc_visibility_map(&map);
+        assert!(!before[[2, 2]]);
+        assert!(before[[2, 1]]);
+
+        let after = visibility_after_raise(&map, 2, 2, 9);
+        assert!(after[[2, 2]]);
+        // the 5 left of it could only be seen from the right
+        assert!(!after[[2, 1]]);
+        assert_eq!(after.iter().filter(|v| **v).count(), 21);
+
+        let mut raised = map.clone();
+        raised[[2, 2]] = 9;
+        assert_eq!(after, calc_visibility_map(&raised));
+        assert_eq!(visibility_after_raise(&map, 2, 2, 3), before);
+    }
+
+    #[test]
+    fn render() {
+        let map = parse_input("30373
+                               25512
+                               65332
+                               33549
+                               35390");
+        let vis_map = calc_visibility_map(&map);
+        let rendered = render_visibility(&map, &vis_map);
+        assert_eq!(rendered.lines().count(), 5);
+        assert_eq!(rendered.matches('[').count(), 21);
+        assert!(rendered.starts_with("[3][0][3][7][3]\n[2][5][5] 1 [2]\n"));
+    }
+
+    #[test]
+    fn maxima() {
+        let input = "30373
+                     25512
+                     65332
+                     33549
+                     35390";
+        let map = parse_input(input);
+        assert_eq!(row_maxima(&map), &[7, 5, 6, 9, 9]);
+        assert_eq!(col_maxima(&map), &[6, 5, 5, 9, 9]);
+
+        let expected = arr2(&[
+            [false, false, false,  true, false],
+            [false,  true,  true, false, false],
+            [ true,  true, false, false, false],
+            [false, false,  true, false,  true],
+            [false,  true, false,  true, false]]);
+        assert_eq!(calc_visibility_map_toroidal(&map), expected);
     }
 }
@@ -1,7 +1,10 @@
 
+use crate::common::Puzzle;
+
 use cgmath::Zero;
 
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
 use std::str::FromStr;
 
 use strum::EnumString;
@@ -10,15 +13,15 @@ use strum::EnumString;
 type Vector = cgmath::Vector2<i32>;
 
 
-#[derive(Debug, Copy, Clone, EnumString)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, EnumString)]
 enum Direction {
-    #[strum(serialize = "U")]
+    #[strum(serialize = "U", serialize = "Up", serialize = "↑")]
     Up,
-    #[strum(serialize = "D")]
+    #[strum(serialize = "D", serialize = "Down", serialize = "↓")]
     Down,
-    #[strum(serialize = "L")]
+    #[strum(serialize = "L", serialize = "Left", serialize = "←")]
     Left,
-    #[strum(serialize = "R")]
+    #[strum(serialize = "R", serialize = "Right", serialize = "→")]
     Right,
 }
 
@@ -34,6 +37,61 @@ impl Direction {
 }
 
 
+fn step_map(tail_delta: Vector) -> Option<Vector> {
+    // this is the map for X. for Y, it's just transposed.
+    static STEP_MAP: [[i32; 5]; 5] = [
+        [-1, -1, 0, 1, 1],
+        [-1,  0, 0, 0, 1],
+        [-1,  0, 0, 0, 1],
+        [-1,  0, 0, 0, 1],
+        [-1, -1, 0, 1, 1],
+    ];
+
+    let offset = (tail_delta + Vector::new(2, 2)).cast::<usize>().unwrap();
+
+    let x = STEP_MAP.get(offset.y)?.get(offset.x)?;
+    let y = STEP_MAP.get(offset.x)?.get(offset.y)?;
+
+    Some(Vector::new(*x, *y))
+}
+
+/// Alternative to `step_map` for tails that may only move orthogonally. If the tail is not
+/// touching the head, it takes a single step along the axis with the greater distance, preferring X
+/// on a tie.
+fn follow4(tail_delta: Vector) -> Vector {
+    let (dx, dy) = (tail_delta.x, tail_delta.y);
+    if dx.abs() <= 1 && dy.abs() <= 1 {
+        Vector::zero()
+    } else if dx.abs() >= dy.abs() {
+        Vector::new(dx.signum(), 0)
+    } else {
+        Vector::new(0, dy.signum())
+    }
+}
+
+/// Moves the first knot in the given direction and lets all others follow.
+fn step_knots(knots: &mut [Vector], direction: Direction) {
+    step_knots_with(knots, direction, |delta| step_map(delta).expect("Oh no the rope broke"));
+}
+
+/// Same as `step_knots`, but each knot follows the one before it according to the given rule.
+#[allow(unused_assignments)]
+fn step_knots_with<F>(knots: &mut [Vector], direction: Direction, follow: F)
+where
+    F: Fn(Vector) -> Vector,
+{
+    let mut step_delta = direction.delta();
+    knots[0] += step_delta;
+    for head_index in 0..(knots.len() - 1) {
+        let tail_index = head_index + 1;
+        let tail_delta = knots[head_index] - knots[tail_index];
+        let tail_step = follow(tail_delta);
+        knots[tail_index] += tail_step;
+        step_delta = tail_step;
+    }
+}
+
+
 /// A rope of N knots.
 struct Rope<const N: usize> {
     knots: [Vector; N],
@@ -46,40 +104,50 @@ impl<const N: usize> Rope<N> {
         }
     }
 
-    fn step_map(tail_delta: Vector) -> Option<Vector> {
-        // this is the map for X. for Y, it's just transposed.
-        static STEP_MAP: [[i32; 5]; 5] = [
-            [-1, -1, 0, 1, 1],
-            [-1,  0, 0, 0, 1],
-            [-1,  0, 0, 0, 1],
-            [-1,  0, 0, 0, 1],
-            [-1, -1, 0, 1, 1],
-        ];
+    fn step(&mut self, direction: Direction) {
+        step_knots(&mut self.knots, direction);
+    }
 
-        let offset = (tail_delta + Vector::new(2, 2)).cast::<usize>().unwrap();
+    #[allow(dead_code)]
+    fn head(&self) -> Vector {
+        *self.knots.first().unwrap()
+    }
 
-        let x = STEP_MAP.get(offset.y)?.get(offset.x)?;
-        let y = STEP_MAP.get(offset.x)?.get(offset.y)?;
+    fn tail(&self) -> Vector {
+        *self.knots.last().unwrap()
+    }
+}
 
-        Some(Vector::new(*x, *y))
+/// Finds all pairs of knots that occupy the same position.
+#[allow(dead_code)]
+fn overlapping_knots<const N: usize>(rope: &Rope<N>) -> Vec<(usize, usize)> {
+    let mut pairs = Vec::new();
+    for (i, a) in rope.knots.iter().enumerate() {
+        for (j, b) in rope.knots.iter().enumerate().skip(i + 1) {
+            if a == b {
+                pairs.push((i, j));
+            }
+        }
     }
+    pairs
+}
 
-    #[allow(unused_assignments)]
-    fn step(&mut self, direction: Direction) {
-        let mut step_delta = direction.delta();
-        self.knots[0] += step_delta;
-        for head_index in 0..(N-1) {
-            let tail_index = head_index + 1;
-            let tail_delta = self.knots[head_index] - self.knots[tail_index];
-            let tail_step = Self::step_map(tail_delta).expect("Oh no the rope broke");
-            self.knots[tail_index] += tail_step;
-            step_delta = tail_step;
+
+/// A rope with a number of knots only known at runtime.
+struct DynRope {
+    knots: Vec<Vector>,
+}
+
+impl DynRope {
+    fn new(len: usize) -> Self {
+        assert!(len > 0, "Rope needs at least one knot");
+        Self {
+            knots: vec![Vector::zero(); len],
         }
     }
 
-    #[allow(dead_code)]
-    fn head(&self) -> Vector {
-        *self.knots.first().unwrap()
+    fn step(&mut self, direction: Direction) {
+        step_knots(&mut self.knots, direction);
     }
 
     fn tail(&self) -> Vector {
@@ -87,16 +155,34 @@ impl<const N: usize> Rope<N> {
     }
 }
 
+#[derive(Debug, PartialEq, Eq)]
+enum Day9Error {
+    BadDirection,
+    BadCount { line: usize },
+}
+
+/// Parses the list of moves. Moves with a count of zero are dropped.
+fn try_parse_input(input: &str) -> Result<Vec<(Direction, usize)>, Day9Error> {
+    let mut moves = Vec::new();
+    for (line_index, line) in input.lines().enumerate() {
+        let (dir_str, count_str) = line.trim()
+            .split_once(' ')
+            .ok_or(Day9Error::BadCount { line: line_index })?;
+        let dir = Direction::from_str(dir_str).map_err(|_| Day9Error::BadDirection)?;
+        let count = count_str.parse().map_err(|_| Day9Error::BadCount { line: line_index })?;
+        if count > 0 {
+            moves.push((dir, count));
+        }
+    }
+    Ok(moves)
+}
+
 fn parse_input(input: &str) -> Vec<(Direction, usize)> {
-    input.lines().map(|line| {
-        let (dir_str, count_str) = line.trim().split_once(' ').unwrap();
-        let dir = Direction::from_str(dir_str).unwrap();
-        let count = count_str.parse().unwrap();
-        (dir, count)
-    }).collect()
+    try_parse_input(input).unwrap()
 }
 
-fn count_visited<const N: usize>(input: &str) -> usize {
+/// Counts how many steps the tail spent on each position.
+fn tail_visits<const N: usize>(input: &str) -> HashMap<Vector, usize> {
     // no need to insert start position. first step will never move the tail
     let instructions = parse_input(input);
     let mut rope = Rope::<N>::new();
@@ -104,21 +190,140 @@ fn count_visited<const N: usize>(input: &str) -> usize {
     for (dir, count) in instructions {
         for _ in 0..count {
             rope.step(dir);
-            map.insert(rope.tail(), true);
+            *map.entry(rope.tail()).or_insert(0) += 1;
         }
     }
-    map.len()
+    map
 }
 
+/// Positions of the head and the tail after every single step, in order.
+#[allow(dead_code)]
+fn head_and_tail_paths<const N: usize>(input: &str) -> (Vec<Vector>, Vec<Vector>) {
+    let instructions = parse_input(input);
+    let mut rope = Rope::<N>::new();
+    let mut head_path = Vec::new();
+    let mut tail_path = Vec::new();
+    for (dir, count) in instructions {
+        for _ in 0..count {
+            rope.step(dir);
+            head_path.push(rope.head());
+            tail_path.push(rope.tail());
+        }
+    }
+    (head_path, tail_path)
+}
+
+fn count_visited<const N: usize>(input: &str) -> usize {
+    tail_visits::<N>(input).len()
+}
+
+/// Like `count_visited`, but knots can only follow orthogonally (see `follow4`).
+#[allow(dead_code)]
+fn count_visited_4connected<const N: usize>(input: &str) -> usize {
+    let instructions = parse_input(input);
+    let mut knots = [Vector::zero(); N];
+    let mut visited = HashSet::from([knots[N - 1]]);
+    for (dir, count) in instructions {
+        for _ in 0..count {
+            step_knots_with(&mut knots, dir, follow4);
+            visited.insert(knots[N - 1]);
+        }
+    }
+    visited.len()
+}
+
+/// Finds the position the tail spent the most steps on. Ties are broken in favor of the topmost,
+/// then leftmost position.
+#[allow(dead_code)]
+fn most_visited_tail_cell<const N: usize>(input: &str) -> (Vector, usize) {
+    tail_visits::<N>(input).into_iter()
+        .max_by_key(|(pos, visits)| (*visits, Reverse((pos.y, pos.x))))
+        .unwrap()
+}
+
+/// Counts the cells enclosed by the tail's path, treating the visited cells as walls. Cells are
+/// enclosed if they can't be reached from outside the path's bounding box by orthogonal steps.
+#[allow(dead_code)]
+fn enclosed_area<const N: usize>(input: &str) -> usize {
+    let visited = tail_visits::<N>(input).into_keys().collect::<HashSet<_>>();
+
+    // bounding box with a margin of one, so the outside is connected all around the path
+    let min = visited.iter()
+        .fold(Vector::zero(), |min, p| Vector::new(min.x.min(p.x), min.y.min(p.y)))
+        - Vector::new(1, 1);
+    let max = visited.iter()
+        .fold(Vector::zero(), |max, p| Vector::new(max.x.max(p.x), max.y.max(p.y)))
+        + Vector::new(1, 1);
+    let in_bounds = |p: Vector| p.x >= min.x && p.x <= max.x && p.y >= min.y && p.y <= max.y;
+
+    let mut outside = HashSet::from([min]);
+    let mut stack = vec![min];
+    while let Some(p) = stack.pop() {
+        for dir in [Direction::Up, Direction::Down, Direction::Left, Direction::Right] {
+            let next = p + dir.delta();
+            if in_bounds(next) && !visited.contains(&next) && outside.insert(next) {
+                stack.push(next);
+            }
+        }
+    }
+
+    let box_area = ((max.x - min.x + 1) * (max.y - min.y + 1)) as usize;
+    box_area - outside.len() - visited.len()
+}
+
+/// Runs the simulation and checks that the tail visited exactly the given positions, e.g. the ones
+/// from the puzzle's diagrams. Duplicates and order in `expected_tail_positions` don't matter.
+#[allow(dead_code)]
+fn verify_tail_log<const N: usize>(input: &str, expected_tail_positions: &[Vector]) -> bool {
+    let visited = tail_visits::<N>(input).into_keys().collect::<HashSet<_>>();
+    let expected = expected_tail_positions.iter().copied().collect::<HashSet<_>>();
+    visited == expected
+}
+
+/// Like `count_visited`, but for multiple rope lengths at once. Only parses the input once.
+#[allow(dead_code)]
+fn count_visited_range(input: &str, lengths: &[usize]) -> Vec<usize> {
+    let instructions = parse_input(input);
+    lengths.iter()
+        .map(|&len| {
+            let mut rope = DynRope::new(len);
+            let mut map = HashMap::new();
+            for &(dir, count) in &instructions {
+                for _ in 0..count {
+                    rope.step(dir);
+                    map.insert(rope.tail(), true);
+                }
+            }
+            map.len()
+        })
+        .collect()
+}
 
 static INPUT: &str = include_str!("inputs/day9.txt");
 
-pub fn run() {
-    let part1 = count_visited::<2>(INPUT);
-    println!("Positions visited by tail on a rope of length 2: {part1}");
 
-    let part2 = count_visited::<10>(INPUT);
-    println!("Positions visited by tail on a rope of length 10: {part2}");
+pub struct Day9;
+
+impl Puzzle for Day9 {
+    fn input(&self) -> &'static str {
+        INPUT
+    }
+
+    fn part1(&self, input: &str) -> String {
+        count_visited::<2>(input).to_string()
+    }
+
+    fn part2(&self, input: &str) -> String {
+        count_visited::<10>(input).to_string()
+    }
+
+    fn run(&self) {
+        let part1 = count_visited::<2>(INPUT);
+        println!("Positions visited by tail on a rope of length 2: {part1}");
+
+        let part2 = count_visited::<10>(INPUT);
+        println!("Positions visited by tail on a rope of length 10: {part2}");
+    }
 }
 
 
@@ -153,6 +358,31 @@ mod test {
         assert_eq!(rope.tail(), Vector::new(2, -1));
     }
 
+    #[test]
+    fn overlaps() {
+        let mut rope = Rope::<4>::new();
+        assert_eq!(overlapping_knots(&rope), &[(0, 1), (0, 2), (0, 3), (1, 2), (1, 3), (2, 3)]);
+
+        rope.step(Direction::Right);
+        assert_eq!(overlapping_knots(&rope), &[(1, 2), (1, 3), (2, 3)]);
+
+        rope.step(Direction::Right);
+        assert_eq!(overlapping_knots(&rope), &[(2, 3)]);
+
+        rope.step(Direction::Right);
+        assert!(overlapping_knots(&rope).is_empty());
+    }
+
+    #[test]
+    fn orthogonal_follow() {
+        assert_eq!(follow4(Vector::new(1, 1)), Vector::zero());
+        assert_eq!(follow4(Vector::new(0, -1)), Vector::zero());
+        assert_eq!(follow4(Vector::new(2, 0)), Vector::new(1, 0));
+        assert_eq!(follow4(Vector::new(1, -2)), Vector::new(0, -1));
+        assert_eq!(follow4(Vector::new(-2, 1)), Vector::new(-1, 0));
+        assert_eq!(follow4(Vector::new(-2, 2)), Vector::new(-1, 0));
+    }
+
     #[test]
     fn example() {
         let input = "R 4
@@ -169,6 +399,28 @@ mod test {
         let count = count_visited::<10>(input);
         assert_eq!(count, 1);
 
+        let counts = count_visited_range(input, &[2, 10]);
+        assert_eq!(counts, &[13, 1]);
+
+        // without diagonal moves, the tail trails further behind and visits fewer positions here
+        assert_eq!(count_visited_4connected::<2>(input), 10);
+        assert_eq!(count_visited_4connected::<10>(input), 1);
+
+        let most_visited = most_visited_tail_cell::<2>(input);
+        assert_eq!(most_visited, (Vector::new(4, -3), 6));
+
+        let (head_path, tail_path) = head_and_tail_paths::<2>(input);
+        let total_moves = parse_input(input).iter().map(|(_, count)| count).sum::<usize>();
+        assert_eq!(head_path.len(), total_moves);
+        assert_eq!(tail_path.len(), total_moves);
+        assert_eq!(head_path.last(), Some(&Vector::new(2, -2)));
+        assert_eq!(tail_path.last(), Some(&Vector::new(1, -2)));
+        assert_eq!(tail_path.iter().collect::<HashSet<_>>().len(), 13);
+
+        // the tail of the long rope never moves
+        let most_visited = most_visited_tail_cell::<10>(input);
+        assert_eq!(most_visited, (Vector::new(0, 0), 24));
+
         let input = "R 5
                      U 8
                      L 8
@@ -179,5 +431,93 @@ mod test {
                      U 20";
         let count = count_visited::<10>(input);
         assert_eq!(count, 36);
+
+        let counts = count_visited_range(input, &[2, 10]);
+        assert_eq!(counts, &[88, 36]);
+    }
+
+    #[test]
+    fn enclosed() {
+        let square = "R 4
+                      U 4
+                      L 4
+                      D 4";
+        // a single knot traces the square exactly
+        assert_eq!(enclosed_area::<1>(square), 9);
+
+        // the tail cuts the corners. diagonal gaps don't let the outside in
+        let square = "R 5
+                      U 5
+                      L 5
+                      D 5";
+        assert_eq!(enclosed_area::<2>(square), 16);
+
+        let input = "R 4
+                     U 4
+                     L 3
+                     D 1
+                     R 4
+                     D 1
+                     L 5
+                     R 2";
+        assert_eq!(enclosed_area::<2>(input), 0);
+    }
+
+    #[test]
+    fn tail_log() {
+        let input = "R 4
+                     U 4
+                     L 3
+                     D 1
+                     R 4
+                     D 1
+                     L 5
+                     R 2";
+        // the diagram from the puzzle, top row first. up is negative y here
+        let diagram = [
+            "..##..",
+            "...##.",
+            ".####.",
+            "....#.",
+            "s###..",
+        ];
+        let expected = diagram.iter()
+            .rev()
+            .enumerate()
+            .flat_map(|(y, row)| row.char_indices()
+                .filter(|(_, c)| *c != '.')
+                .map(move |(x, _)| Vector::new(x as i32, -(y as i32))))
+            .collect::<Vec<_>>();
+        assert_eq!(expected.len(), 13);
+        assert!(verify_tail_log::<2>(input, &expected));
+
+        assert!(!verify_tail_log::<2>(input, &expected[1..]));
+        assert!(!verify_tail_log::<10>(input, &expected));
+        assert!(verify_tail_log::<10>(input, &[Vector::zero()]));
+    }
+
+    #[test]
+    fn parse_errors() {
+        let moves = try_parse_input("R 4\nU 0\nL 3").unwrap();
+        assert_eq!(moves.len(), 2);
+
+        assert_eq!(try_parse_input("R 4\nX 4"), Err(Day9Error::BadDirection));
+        assert_eq!(try_parse_input("R 4\nU four"), Err(Day9Error::BadCount { line: 1 }));
+        assert_eq!(try_parse_input("R 1.5"), Err(Day9Error::BadCount { line: 0 }));
+        assert_eq!(try_parse_input("R"), Err(Day9Error::BadCount { line: 0 }));
+    }
+
+    #[test]
+    fn direction_aliases() {
+        let moves = parse_input("R 4
+                                 Right 4
+                                 → 4");
+        assert_eq!(moves, &[(Direction::Right, 4); 3]);
+
+        let moves = parse_input("U 1\nDown 2\n← 3\nL 4\n↑ 5");
+        assert_eq!(moves, &[(Direction::Up, 1), (Direction::Down, 2), (Direction::Left, 3),
+            (Direction::Left, 4), (Direction::Up, 5)]);
+
+        assert_eq!(try_parse_input("right 4"), Err(Day9Error::BadDirection));
     }
 }
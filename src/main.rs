@@ -17,6 +17,8 @@ pub mod day14;
 pub mod day15;
 
 
+use common::Puzzle;
+
 use clap::Parser;
 
 use colored::Colorize;
@@ -32,9 +34,6 @@ struct Args {
 }
 
 
-type AocFunction = fn() -> ();
-
-
 #[derive(Debug)]
 enum AocError {
     NotYetSolved,
@@ -42,15 +41,14 @@ enum AocError {
 }
 
 
-#[derive(Debug)]
-struct Aoc([Option<AocFunction>; 24]);
+struct Aoc([Option<Box<dyn Puzzle>>; 24]);
 
 impl Aoc {
     pub const DAY_RANGE: RangeInclusive<usize> = 1..=24;
 
-    fn add_day(&mut self, day: usize, f: AocFunction) -> Result<(), AocError> {
+    fn add_day(&mut self, day: usize, puzzle: Box<dyn Puzzle>) -> Result<(), AocError> {
         if Self::DAY_RANGE.contains(&day) {
-            self.0[day - 1] = Some(f);
+            self.0[day - 1] = Some(puzzle);
             Ok(())
         } else {
             Err(AocError::InvalidDay)
@@ -58,37 +56,38 @@ impl Aoc {
     }
 
     pub fn new() -> Result<Self, AocError> {
-        let mut aoc = Self([None; 24]);
-        aoc.add_day(1, day1::run)?;
-        aoc.add_day(2, day2::run)?;
-        aoc.add_day(3, day3::run)?;
-        aoc.add_day(4, day4::run)?;
-        aoc.add_day(5, day5::run)?;
-        aoc.add_day(6, day6::run)?;
-        aoc.add_day(7, day7::run)?;
-        aoc.add_day(8, day8::run)?;
-        aoc.add_day(9, day9::run)?;
-        aoc.add_day(10, day10::run)?;
-        aoc.add_day(11, day11::run)?;
-        aoc.add_day(12, day12::run)?;
-        aoc.add_day(13, day13::run)?;
-        aoc.add_day(14, day14::run)?;
-        aoc.add_day(15, day15::run)?;
+        let mut aoc = Self(Default::default());
+        aoc.add_day(1, Box::new(day1::Day1))?;
+        aoc.add_day(2, Box::new(day2::Day2))?;
+        aoc.add_day(3, Box::new(day3::Day3))?;
+        aoc.add_day(4, Box::new(day4::Day4))?;
+        aoc.add_day(5, Box::new(day5::Day5))?;
+        aoc.add_day(6, Box::new(day6::Day6))?;
+        aoc.add_day(7, Box::new(day7::Day7))?;
+        aoc.add_day(8, Box::new(day8::Day8))?;
+        aoc.add_day(9, Box::new(day9::Day9))?;
+        aoc.add_day(10, Box::new(day10::Day10))?;
+        aoc.add_day(11, Box::new(day11::Day11))?;
+        aoc.add_day(12, Box::new(day12::Day12))?;
+        aoc.add_day(13, Box::new(day13::Day13))?;
+        aoc.add_day(14, Box::new(day14::Day14))?;
+        aoc.add_day(15, Box::new(day15::Day15))?;
         Ok(aoc)
     }
 
-    pub fn get_day(&self, day: usize) -> Result<AocFunction, AocError> {
-        let day_fn = self.0.get(day.wrapping_sub(1))
+    pub fn get_day(&self, day: usize) -> Result<&dyn Puzzle, AocError> {
+        let puzzle = self.0.get(day.wrapping_sub(1))
             .ok_or(AocError::InvalidDay)?
+            .as_deref()
             .ok_or(AocError::NotYetSolved)?;
-        Ok(day_fn)
+        Ok(puzzle)
     }
 
     pub fn run_day(&self, day: usize) {
         match self.get_day(day) {
-            Ok(day_fn) => {
+            Ok(puzzle) => {
                 eprintln!("{} {day}", "Running day".green().bold());
-                day_fn();
+                puzzle.run();
             },
             Err(AocError::NotYetSolved) => {
                 eprintln!("{} {day} {}", "Day".red().bold(), "not yet solved".red().bold());
@@ -120,3 +119,21 @@ fn main() {
         aoc.run_all_days();
     }
 }
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn puzzle_trait_object() {
+        let input = "1000\n2000\n3000\n\n4000\n\n5000\n6000\n\n7000\n8000\n9000\n\n10000\n";
+        let aoc = Aoc::new().unwrap();
+        let day1 = aoc.get_day(1).unwrap();
+        assert_eq!(day1.part1(input), "24000");
+        assert_eq!(day1.part2(input), "45000");
+
+        assert!(matches!(aoc.get_day(16), Err(AocError::NotYetSolved)));
+        assert!(matches!(aoc.get_day(0), Err(AocError::InvalidDay)));
+    }
+}